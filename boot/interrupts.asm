@@ -0,0 +1,83 @@
+global isr_table
+
+extern interrupt_dispatch
+
+section .text
+bits 64
+
+    ;; entry stub for one interrupt vector, it pushes a dummy error code,
+    ;; if the cpu does not push one, so that all vectors have the same stack layout
+%macro isr 1
+isr_%+%1:
+%if %1 == 8 || (%1 >= 10 && %1 <= 14) || %1 == 17 || %1 == 21 || %1 == 29 || %1 == 30
+%else
+    push 0
+%endif
+    push %1
+    jmp isr_common
+%endmacro
+
+%assign i 0
+%rep 256
+    isr i
+%assign i i+1
+%endrep
+
+    ;; saves all registers and calls interrupt_dispatch with a pointer to them,
+    ;; must match InterruptFrame in src/interrupts/mod.rs
+isr_common:
+    push rax
+    push rbx
+    push rcx
+    push rdx
+    push rsi
+    push rdi
+    push rbp
+    push r8
+    push r9
+    push r10
+    push r11
+    push r12
+    push r13
+    push r14
+    push r15
+
+    ;; rust uses the SSE registers, the stack is 16 byte aligned here
+    sub rsp, 512
+    fxsave [rsp]
+
+    lea rdi, [rsp + 512]
+    cld
+    call interrupt_dispatch
+
+    fxrstor [rsp]
+    add rsp, 512
+
+    pop r15
+    pop r14
+    pop r13
+    pop r12
+    pop r11
+    pop r10
+    pop r9
+    pop r8
+    pop rbp
+    pop rdi
+    pop rsi
+    pop rdx
+    pop rcx
+    pop rbx
+    pop rax
+
+    ;;  remove vector and error code
+    add rsp, 16
+    iretq
+
+section .rodata
+    ;; addresses of all stubs, used to build the IDT
+isr_table:
+%assign i 0
+%rep 256
+    dq isr_%+i
+%assign i i+1
+%endrep
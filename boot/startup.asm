@@ -1,4 +1,5 @@
 global startup
+global stack_guard
 section .multiboot_header
 header_start:
     dd 0xe85250d6                ; magic number (multiboot 2)
@@ -190,6 +191,9 @@ p3_table:
     resb 4096
 p2_table:
     resb 4096
+    ;; not mapped after the kernel is remapped, so a stack overflow causes a page fault
+stack_guard:
+    resb 4096
 stack_bottom:
     resb 4096 * 8
 stack_top:
@@ -1,5 +1,6 @@
 use core::fmt;
 use spin::Mutex;
+use io_port::IOPort;
 
 #[allow(dead_code)]
 pub enum Color {
@@ -14,8 +15,21 @@ const CGA_START: u64 = 0xb8000;
 pub const COLUMNS: u64 = 80;
 pub const ROWS: u64 = 25;
 
-pub static DBG: Mutex<CGAScreen> = Mutex::new(CGAScreen::new_const(0, 0, COLUMNS, 2));
-pub static SCREEN: Mutex<CGAScreen> = Mutex::new(CGAScreen::new_const(0, 2, COLUMNS, ROWS - 2));
+// crt controller
+static CRTC_INDEX: IOPort = IOPort::new(0x3d4);
+static CRTC_DATA: IOPort = IOPort::new(0x3d5);
+const CURSOR_START: u8 = 0x0a;
+const CURSOR_END: u8 = 0x0b;
+const CURSOR_POS_HIGH: u8 = 0x0e;
+const CURSOR_POS_LOW: u8 = 0x0f;
+const CURSOR_DISABLED: u8 = 1 << 5;
+const CURSOR_SCANLINE_MASK: u8 = 0x1f;
+// a character cell of the text mode is 16 scanlines high
+const MAX_SCANLINE: u8 = 15;
+
+pub static DBG: Mutex<CGAScreen> = Mutex::new(CGAScreen::new_const(0, 0, COLUMNS, 2, false));
+// the hardware cursor follows the main screen
+pub static SCREEN: Mutex<CGAScreen> = Mutex::new(CGAScreen::new_const(0, 2, COLUMNS, ROWS - 2, true));
 
 macro_rules! println {
     ($screen:expr, $fmt:expr) => (print!($screen, concat!($fmt, "\n")));
@@ -28,6 +42,7 @@ macro_rules! print {
     });
 }
 
+#[allow(unused_macros)]
 macro_rules! dbg {
     ($($arg:tt)*) => ({
         $crate::cga_screen::dbg(format_args!($($arg)*));
@@ -45,22 +60,25 @@ pub struct CGAScreen {
     from_col: u64, from_row: u64,
     size_x: u64, size_y: u64,
     cursor_x: u64, cursor_y: u64,
-    color: u8
+    color: u8,
+    hw_cursor: bool,
 }
 
 impl CGAScreen {
-    const fn new_const(from_col: u64, from_row: u64, size_x: u64, size_y: u64) -> CGAScreen {
+    const fn new_const(from_col: u64, from_row: u64, size_x: u64, size_y: u64,
+                       hw_cursor: bool) -> CGAScreen {
         CGAScreen{from_col: from_col, from_row: from_row,
                   size_x: size_x, size_y: size_y,
                   cursor_x: 0, cursor_y: 0,
-                  color: STD_ATTR}
+                  color: STD_ATTR,
+                  hw_cursor: hw_cursor}
     }
 
     #[allow(dead_code)]
     pub fn new(from_col: u64, from_row: u64, size_x: u64, size_y: u64) -> CGAScreen {
         assert!(from_col + size_x <= COLUMNS);
         assert!(from_row + size_y <= ROWS);
-        CGAScreen::new_const(from_col, from_row, size_x, size_y)
+        CGAScreen::new_const(from_col, from_row, size_x, size_y, false)
     }
 
     pub fn show(&mut self, x: u64, y: u64, b: u8) {
@@ -104,6 +122,7 @@ impl CGAScreen {
             self.cursor_y -= 1;
             self.cursor_x = 0;
         }
+        self.update_hw_cursor();
     }
 
     pub fn scroll_down(&mut self, amount: u64) {
@@ -127,6 +146,7 @@ impl CGAScreen {
         assert!(y < self.size_y);
         self.cursor_x = x;
         self.cursor_y = y;
+        self.update_hw_cursor();
     }
 
     pub fn clear(&mut self) {
@@ -143,17 +163,72 @@ impl CGAScreen {
     pub fn set_color(&mut self, fg: Color, bg: Color) {
         self.color = build_color(fg, bg);
     }
+
+    pub fn show_cursor(&mut self) {
+        assert!(self.hw_cursor, "screen has no hardware cursor");
+        let start = read_crtc(CURSOR_START);
+        write_crtc(CURSOR_START, start & !CURSOR_DISABLED);
+        self.update_hw_cursor();
+    }
+
+    #[allow(dead_code)]
+    pub fn hide_cursor(&mut self) {
+        assert!(self.hw_cursor, "screen has no hardware cursor");
+        let start = read_crtc(CURSOR_START);
+        write_crtc(CURSOR_START, start | CURSOR_DISABLED);
+    }
+
+    // the cursor covers the scanlines start to end of a character cell (0 to 15)
+    pub fn set_cursor_shape(&mut self, start: u8, end: u8) {
+        assert!(self.hw_cursor, "screen has no hardware cursor");
+        assert!(start <= end && end <= MAX_SCANLINE);
+        let old_start = read_crtc(CURSOR_START);
+        write_crtc(CURSOR_START, (old_start & !CURSOR_SCANLINE_MASK) | start);
+        let old_end = read_crtc(CURSOR_END);
+        write_crtc(CURSOR_END, (old_end & !CURSOR_SCANLINE_MASK) | end);
+    }
+
+    fn update_hw_cursor(&self) {
+        if !self.hw_cursor {
+            return;
+        }
+        let pos = (self.cursor_y + self.from_row) * COLUMNS + (self.cursor_x + self.from_col);
+        write_crtc(CURSOR_POS_HIGH, (pos >> 8) as u8);
+        write_crtc(CURSOR_POS_LOW, pos as u8);
+    }
 }
 
 impl fmt::Write for CGAScreen {
     fn write_str(&mut self, s: &str) -> ::core::fmt::Result {
-        for byte in s.bytes() {
-            self.write_byte(byte)
+        for c in s.chars() {
+            self.write_byte(to_code_page_437(c))
         }
         Ok(())
     }
 }
 
+// the CGA font uses code page 437
+fn to_code_page_437(c: char) -> u8 {
+    match c {
+        '\0'...'\x7f' => c as u8,
+        'ä' => 132, 'ö' => 148, 'ü' => 129,
+        'Ä' => 142, 'Ö' => 153, 'Ü' => 154,
+        'ß' => 225, '§' => 21, '°' => 248,
+        '²' => 253, 'µ' => 230,
+        _ => b'?',
+    }
+}
+
+fn read_crtc(register: u8) -> u8 {
+    CRTC_INDEX.outb(register);
+    CRTC_DATA.inb()
+}
+
+fn write_crtc(register: u8, value: u8) {
+    CRTC_INDEX.outb(register);
+    CRTC_DATA.outb(value);
+}
+
 const fn build_color(fg: Color, bg: Color) -> u8 {
     ((bg as u8 & 0x7) << 4) | (fg as u8 & 0xf)
 }
@@ -0,0 +1,28 @@
+use multiboot2::BootInformation;
+use spin::Once;
+
+// the command line passed by the boot loader, e.g. "keymap=de log=info"
+static COMMAND_LINE: Once<&'static str> = Once::new();
+
+pub fn init(boot_info: &'static BootInformation) {
+    COMMAND_LINE.call_once(|| {
+        boot_info.command_line_tag()
+            .map(|tag| tag.command_line())
+            .unwrap_or("")
+    });
+}
+
+pub fn command_line() -> &'static str {
+    COMMAND_LINE.try().map(|line| *line).unwrap_or("")
+}
+
+// value of the first "key=value" option with the given key
+pub fn option(key: &str) -> Option<&'static str> {
+    for option in command_line().split_whitespace() {
+        let mut parts = option.splitn(2, '=');
+        if parts.next() == Some(key) {
+            return Some(parts.next().unwrap_or(""));
+        }
+    }
+    None
+}
@@ -0,0 +1,139 @@
+use core::ptr::{read_volatile, write_volatile};
+use core::sync::atomic::{AtomicUsize, ATOMIC_USIZE_INIT, Ordering};
+use x86::shared::msr;
+use memory::{FrameAllocator, PageTable, map_device_memory};
+use super::pic::{MASTER_OFFSET, IRQ_COUNT};
+
+const APIC_BASE_MSR: u32 = 0x1b;
+const APIC_BASE_MASK: u64 = 0x000f_ffff_ffff_f000;
+const APIC_GLOBAL_ENABLE: u64 = 1 << 11;
+const CPUID_APIC: u32 = 1 << 9;
+
+// local apic registers
+const TASK_PRIORITY: usize = 0x80;
+const END_OF_INTERRUPT: usize = 0xb0;
+const SPURIOUS_VECTOR: usize = 0xf0;
+const APIC_ENABLE: u32 = 1 << 8;
+pub const SPURIOUS_INTERRUPT: u32 = 0xff;
+
+// there is no acpi support yet, so the io apic is expected at the default address
+const IO_APIC_DEFAULT_BASE: usize = 0xfec0_0000;
+const IO_REGISTER_SELECT: usize = 0x00;
+const IO_WINDOW: usize = 0x10;
+const IO_APIC_VERSION: u32 = 0x01;
+const IO_REDIRECTION_TABLE: u32 = 0x10;
+const REDIRECTION_MASKED: u32 = 1 << 16;
+
+// 0 as long as the apic is not used
+static LOCAL_APIC_BASE: AtomicUsize = ATOMIC_USIZE_INIT;
+static IO_APIC_BASE: AtomicUsize = ATOMIC_USIZE_INIT;
+
+pub fn is_present() -> bool {
+    let (_, _, _, edx) = cpuid(1);
+    (edx & CPUID_APIC) != 0
+}
+
+pub fn is_enabled() -> bool {
+    LOCAL_APIC_BASE.load(Ordering::SeqCst) != 0
+}
+
+// enables the local apic and routes the isa irqs through the io apic,
+// returns false if there is no usable apic
+pub fn init<A>(page_table: &mut PageTable, allocator: &mut A) -> bool
+    where A: FrameAllocator {
+    if !is_present() {
+        return false;
+    }
+
+    let base_msr = unsafe { msr::rdmsr(APIC_BASE_MSR) };
+    let local_base = (base_msr & APIC_BASE_MASK) as usize;
+    map_device_memory(page_table, local_base, allocator);
+    map_device_memory(page_table, IO_APIC_DEFAULT_BASE, allocator);
+
+    let version = io_read(IO_APIC_DEFAULT_BASE, IO_APIC_VERSION);
+    let redirection_entries = ((version >> 16) & 0xff) as usize + 1;
+    if version == !0 || redirection_entries < IRQ_COUNT {
+        // no io apic at the default address
+        return false;
+    }
+
+    unsafe { msr::wrmsr(APIC_BASE_MSR, base_msr | APIC_GLOBAL_ENABLE); }
+    local_write(local_base, TASK_PRIORITY, 0);
+    local_write(local_base, SPURIOUS_VECTOR, APIC_ENABLE | SPURIOUS_INTERRUPT);
+
+    // isa irqs go to the same vectors as with the pic, but are masked for now
+    let apic_id = local_id(local_base);
+    for irq in 0..IRQ_COUNT {
+        let (low, high) = (REDIRECTION_MASKED | (MASTER_OFFSET as u32 + irq as u32),
+                           apic_id << 24);
+        io_write(IO_APIC_DEFAULT_BASE, redirection_register(irq), low);
+        io_write(IO_APIC_DEFAULT_BASE, redirection_register(irq) + 1, high);
+    }
+
+    IO_APIC_BASE.store(IO_APIC_DEFAULT_BASE, Ordering::SeqCst);
+    LOCAL_APIC_BASE.store(local_base, Ordering::SeqCst);
+    true
+}
+
+// must be called with interrupts disabled
+pub fn mask(irq: usize) {
+    let base = IO_APIC_BASE.load(Ordering::SeqCst);
+    let low = io_read(base, redirection_register(irq));
+    io_write(base, redirection_register(irq), low | REDIRECTION_MASKED);
+}
+
+// must be called with interrupts disabled
+pub fn unmask(irq: usize) {
+    let base = IO_APIC_BASE.load(Ordering::SeqCst);
+    let low = io_read(base, redirection_register(irq));
+    io_write(base, redirection_register(irq), low & !REDIRECTION_MASKED);
+}
+
+pub fn eoi() {
+    local_write(LOCAL_APIC_BASE.load(Ordering::SeqCst), END_OF_INTERRUPT, 0);
+}
+
+// without the acpi tables the usual override of the pit (irq 0 to pin 2) is assumed,
+// the unused cascade irq 2 takes pin 0, so that every irq has its own pin
+fn redirection_register(irq: usize) -> u32 {
+    let pin = match irq {
+        0 => 2,
+        2 => 0,
+        _ => irq as u32,
+    };
+    IO_REDIRECTION_TABLE + pin * 2
+}
+
+fn local_id(base: usize) -> u32 {
+    const ID: usize = 0x20;
+    unsafe { read_volatile((base + ID) as *const u32) >> 24 }
+}
+
+fn local_write(base: usize, register: usize, value: u32) {
+    unsafe { write_volatile((base + register) as *mut u32, value); }
+}
+
+fn io_read(base: usize, register: u32) -> u32 {
+    unsafe {
+        write_volatile((base + IO_REGISTER_SELECT) as *mut u32, register);
+        read_volatile((base + IO_WINDOW) as *const u32)
+    }
+}
+
+fn io_write(base: usize, register: u32, value: u32) {
+    unsafe {
+        write_volatile((base + IO_REGISTER_SELECT) as *mut u32, register);
+        write_volatile((base + IO_WINDOW) as *mut u32, value);
+    }
+}
+
+fn cpuid(leaf: u32) -> (u32, u32, u32, u32) {
+    let (eax, ebx, ecx, edx);
+    unsafe {
+        asm!("cpuid"
+             : "={eax}" (eax), "={ebx}" (ebx), "={ecx}" (ecx), "={edx}" (edx)
+             : "{eax}" (leaf), "{ecx}" (0)
+             :: "volatile");
+    }
+    (eax, ebx, ecx, edx)
+}
@@ -0,0 +1,73 @@
+use x86::shared::control_regs;
+use cga_screen::{Color, CGAScreen, ROWS, COLUMNS};
+use memory::is_stack_guard;
+use super::InterruptFrame;
+
+const BREAKPOINT: u64 = 3;
+const DOUBLE_FAULT: u64 = 8;
+const PAGE_FAULT: u64 = 14;
+
+static NAMES: [&'static str; 32] = [
+    "Divide Error", "Debug", "Non-maskable Interrupt", "Breakpoint",
+    "Overflow", "Bound Range Exceeded", "Invalid Opcode", "Device Not Available",
+    "Double Fault", "Coprocessor Segment Overrun", "Invalid TSS", "Segment Not Present",
+    "Stack-Segment Fault", "General Protection Fault", "Page Fault", "Reserved",
+    "x87 Floating-Point Exception", "Alignment Check", "Machine Check", "SIMD Floating-Point Exception",
+    "Virtualization Exception", "Control Protection Exception", "Reserved", "Reserved",
+    "Reserved", "Reserved", "Reserved", "Reserved",
+    "Hypervisor Injection Exception", "VMM Communication Exception", "Security Exception", "Reserved",
+];
+
+bitflags! {
+    flags PageFaultErrorCode: u64 {
+        const PROTECTION_VIOLATION = 1 << 0,
+        const CAUSED_BY_WRITE =      1 << 1,
+        const USER_MODE =            1 << 2,
+        const MALFORMED_TABLE =      1 << 3,
+        const INSTRUCTION_FETCH =    1 << 4,
+    }
+}
+
+pub fn handle(frame: &InterruptFrame) {
+    if frame.vector == BREAKPOINT {
+        // harmless, continue after the int3,
+        // DBG might be locked by the interrupted code, so its area is written directly
+        let mut dbg_area = CGAScreen::new(0, 0, COLUMNS, 2);
+        dbg_area.clear();
+        println!(dbg_area, "breakpoint at {:#x}", frame.rip);
+        return;
+    }
+
+    // SCREEN might be locked by the interrupted code, so the whole screen is used directly
+    let mut screen = CGAScreen::new(0, 0, COLUMNS, ROWS);
+    screen.set_color(Color::White, Color::Red);
+    screen.clear();
+
+    println!(screen, "EXCEPTION {}: {}", frame.vector, NAMES[frame.vector as usize]);
+    println!(screen, "error code: {:#x}", frame.error_code);
+    println!(screen, "rip: {:#x}, rsp: {:#x}", frame.rip, frame.rsp);
+
+    if frame.vector == PAGE_FAULT {
+        let address = unsafe { control_regs::cr2() };
+        println!(screen, "accessed address (cr2): {:#x}", address);
+        println!(screen, "{:?}", PageFaultErrorCode::from_bits_truncate(frame.error_code));
+        if is_stack_guard(address) {
+            println!(screen, "kernel stack overflow");
+        }
+    }
+
+    if frame.vector == DOUBLE_FAULT {
+        // a page fault on a guard page can not be handled on the overflowed stack
+        let address = unsafe { control_regs::cr2() };
+        if is_stack_guard(address) {
+            println!(screen, "kernel stack overflow at {:#x}", address);
+        }
+    }
+
+    println!(screen, "");
+    println!(screen, "{:?}", frame);
+
+    loop {
+        unsafe { asm!("cli; hlt" :::: "volatile"); }
+    }
+}
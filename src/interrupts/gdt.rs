@@ -0,0 +1,130 @@
+use core::mem::size_of;
+use spin::Once;
+use super::idt::DescriptorTablePointer;
+
+pub const KERNEL_CODE_SELECTOR: u16 = 1 << 3;
+pub const KERNEL_DATA_SELECTOR: u16 = 2 << 3;
+// the user segments are in the order needed by sysret
+pub const USER_DATA_SELECTOR: u16 = (3 << 3) | 3;
+pub const USER_CODE_SELECTOR: u16 = (4 << 3) | 3;
+const TSS_SELECTOR: u16 = 5 << 3;
+
+pub const DOUBLE_FAULT_IST_INDEX: usize = 0;
+pub const NMI_IST_INDEX: usize = 1;
+
+const ENTRY_COUNT: usize = 8;
+
+bitflags! {
+    flags DescriptorFlags: u64 {
+        const ACCESSED =        1 << 40,
+        const WRITABLE =        1 << 41,
+        const EXECUTABLE =      1 << 43,
+        const USER_SEGMENT =    1 << 44,
+        const DPL_RING_3 =      3 << 45,
+        const PRESENT =         1 << 47,
+        const LONG_MODE =       1 << 53,
+    }
+}
+
+// the accessed bit is set, so that the cpu never has to write to the gdt
+const KERNEL_CODE: u64 = USER_SEGMENT.bits | PRESENT.bits | EXECUTABLE.bits |
+    LONG_MODE.bits | WRITABLE.bits | ACCESSED.bits;
+const KERNEL_DATA: u64 = USER_SEGMENT.bits | PRESENT.bits | WRITABLE.bits | ACCESSED.bits;
+const USER_CODE: u64 = KERNEL_CODE | DPL_RING_3.bits;
+const USER_DATA: u64 = KERNEL_DATA | DPL_RING_3.bits;
+
+// 64 bit TSS descriptor type
+const TSS_AVAILABLE: u64 = 0b1001 << 40;
+
+#[repr(C, packed)]
+pub struct TaskStateSegment {
+    reserved_1: u32,
+    // stacks used when switching from user to kernel mode
+    pub privilege_stack_table: [u64; 3],
+    reserved_2: u64,
+    pub interrupt_stack_table: [u64; 7],
+    reserved_3: u64,
+    reserved_4: u16,
+    iomap_base: u16,
+}
+
+impl TaskStateSegment {
+    fn new() -> TaskStateSegment {
+        TaskStateSegment {
+            reserved_1: 0,
+            privilege_stack_table: [0; 3],
+            reserved_2: 0,
+            interrupt_stack_table: [0; 7],
+            reserved_3: 0,
+            reserved_4: 0,
+            // no io permission bitmap
+            iomap_base: size_of::<TaskStateSegment>() as u16,
+        }
+    }
+}
+
+struct Gdt {
+    table: [u64; ENTRY_COUNT],
+}
+
+impl Gdt {
+    fn new(tss: &'static TaskStateSegment) -> Gdt {
+        let base = tss as *const _ as u64;
+        let limit = (size_of::<TaskStateSegment>() - 1) as u64;
+
+        let mut table = [0; ENTRY_COUNT];
+        table[(KERNEL_CODE_SELECTOR >> 3) as usize] = KERNEL_CODE;
+        table[(KERNEL_DATA_SELECTOR >> 3) as usize] = KERNEL_DATA;
+        table[(USER_DATA_SELECTOR >> 3) as usize] = USER_DATA;
+        table[(USER_CODE_SELECTOR >> 3) as usize] = USER_CODE;
+
+        // the tss descriptor takes two entries
+        let tss_index = (TSS_SELECTOR >> 3) as usize;
+        table[tss_index] = (limit & 0xffff) | ((base & 0xff_ffff) << 16) | TSS_AVAILABLE |
+            PRESENT.bits | (((limit >> 16) & 0xf) << 48) | (((base >> 24) & 0xff) << 56);
+        table[tss_index + 1] = base >> 32;
+
+        Gdt { table: table }
+    }
+
+    // the gdt must not be moved or dropped while it is loaded
+    unsafe fn load(&'static self) {
+        let pointer = DescriptorTablePointer {
+            limit: (size_of::<Self>() - 1) as u16,
+            base: self as *const _ as u64,
+        };
+        asm!("lgdt ($0)" :: "r" (&pointer) : "memory");
+    }
+}
+
+static TSS: Once<TaskStateSegment> = Once::new();
+static GDT: Once<Gdt> = Once::new();
+
+// replaces the gdt from boot/startup.asm and loads the tss, the ist stacks are used
+// for the double fault and non-maskable interrupt handlers
+pub fn init(double_fault_stack_top: usize, nmi_stack_top: usize) {
+    let tss = TSS.call_once(|| {
+        let mut tss = TaskStateSegment::new();
+        tss.interrupt_stack_table[DOUBLE_FAULT_IST_INDEX] = double_fault_stack_top as u64;
+        tss.interrupt_stack_table[NMI_IST_INDEX] = nmi_stack_top as u64;
+        tss
+    });
+    let gdt = GDT.call_once(|| Gdt::new(tss));
+
+    unsafe {
+        gdt.load();
+
+        // reload the code segment with a far return
+        asm!("pushq $0
+              leaq 1f(%rip), %rax
+              pushq %rax
+              lretq
+              1:"
+             :: "ri" (KERNEL_CODE_SELECTOR as u64) : "rax", "memory" : "volatile");
+        asm!("movw $0, %ss
+              movw $0, %ds
+              movw $0, %es"
+             :: "r" (KERNEL_DATA_SELECTOR) :: "volatile");
+        asm!("ltr $0" :: "r" (TSS_SELECTOR) :: "volatile");
+    }
+}
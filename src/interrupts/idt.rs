@@ -0,0 +1,69 @@
+use core::mem::size_of;
+
+pub const ENTRY_COUNT: usize = 256;
+
+// 64 bit interrupt gate, interrupts are disabled while the handler runs
+const INTERRUPT_GATE: u16 = 0xe << 8;
+const PRESENT: u16 = 1 << 15;
+
+#[derive(Clone, Copy)]
+#[repr(C, packed)]
+pub struct Entry {
+    pointer_low: u16,
+    selector: u16,
+    // bits 0-2: interrupt stack table index, bits 8-11: gate type, bit 15: present
+    options: u16,
+    pointer_middle: u16,
+    pointer_high: u32,
+    reserved: u32,
+}
+
+impl Entry {
+    const fn missing() -> Entry {
+        Entry {
+            pointer_low: 0, selector: 0, options: 0,
+            pointer_middle: 0, pointer_high: 0, reserved: 0,
+        }
+    }
+
+    pub fn set_handler(&mut self, handler: u64, selector: u16) {
+        self.pointer_low = handler as u16;
+        self.pointer_middle = (handler >> 16) as u16;
+        self.pointer_high = (handler >> 32) as u32;
+        self.selector = selector;
+        self.options = PRESENT | INTERRUPT_GATE;
+    }
+
+    // the handler runs on the stack from the interrupt stack table of the tss
+    pub fn set_stack_index(&mut self, index: usize) {
+        assert!(index < 7);
+        self.options = (self.options & !0b111) | (index as u16 + 1);
+    }
+}
+
+pub struct Idt([Entry; ENTRY_COUNT]);
+
+impl Idt {
+    pub const fn new() -> Idt {
+        Idt([Entry::missing(); ENTRY_COUNT])
+    }
+
+    pub fn entry_mut(&mut self, vector: usize) -> &mut Entry {
+        &mut self.0[vector]
+    }
+
+    // the idt must not be moved or dropped while it is loaded
+    pub unsafe fn load(&self) {
+        let pointer = DescriptorTablePointer {
+            limit: (size_of::<Self>() - 1) as u16,
+            base: self as *const _ as u64,
+        };
+        asm!("lidt ($0)" :: "r" (&pointer) : "memory");
+    }
+}
+
+#[repr(C, packed)]
+pub struct DescriptorTablePointer {
+    pub limit: u16,
+    pub base: u64,
+}
@@ -0,0 +1,82 @@
+use spin::Mutex;
+use cga_screen::{CGAScreen, COLUMNS};
+use super::{pic, apic, without_interrupts};
+use super::pic::IRQ_COUNT;
+
+// only locked with interrupts disabled, so the lock can not be held when an irq arrives
+static HANDLERS: Mutex<[Option<fn()>; IRQ_COUNT]> = Mutex::new([None; IRQ_COUNT]);
+
+// the handler runs with interrupts disabled and should return fast
+pub fn register_irq(irq: usize, handler: fn()) {
+    assert!(irq < IRQ_COUNT, "invalid irq {}", irq);
+    without_interrupts(|| {
+        let mut handlers = HANDLERS.lock();
+        assert!(handlers[irq].is_none(), "irq {} is already registered", irq);
+        handlers[irq] = Some(handler);
+        unmask(irq);
+    });
+}
+
+#[allow(dead_code)]
+pub fn unregister_irq(irq: usize) {
+    assert!(irq < IRQ_COUNT, "invalid irq {}", irq);
+    without_interrupts(|| {
+        mask(irq);
+        HANDLERS.lock()[irq] = None;
+    });
+}
+
+pub fn dispatch(irq: usize) {
+    let use_apic = apic::is_enabled();
+    if !use_apic && pic::is_spurious(irq) {
+        return;
+    }
+
+    let handler = HANDLERS.lock()[irq];
+    match handler {
+        Some(handler) => handler(),
+        None => {
+            // DBG might be locked by the interrupted code, so its area is written directly
+            let mut dbg_area = CGAScreen::new(0, 0, COLUMNS, 2);
+            dbg_area.clear();
+            println!(dbg_area, "unhandled irq {}", irq);
+        }
+    }
+
+    if use_apic {
+        apic::eoi();
+    } else {
+        pic::eoi(irq);
+    }
+}
+
+// called once the apic is initialized, the registered irqs are now delivered by the io apic
+pub fn switch_to_apic() {
+    without_interrupts(|| {
+        pic::disable();
+        let handlers = HANDLERS.lock();
+        for irq in 0..IRQ_COUNT {
+            if handlers[irq].is_some() {
+                apic::unmask(irq);
+            }
+        }
+    });
+}
+
+// must be called with interrupts disabled
+fn mask(irq: usize) {
+    if apic::is_enabled() {
+        apic::mask(irq);
+    } else {
+        pic::mask(irq);
+    }
+}
+
+// must be called with interrupts disabled
+fn unmask(irq: usize) {
+    if apic::is_enabled() {
+        apic::unmask(irq);
+    } else {
+        pic::unmask(irq);
+    }
+}
@@ -0,0 +1,123 @@
+pub use self::irq::{register_irq, unregister_irq};
+
+mod idt;
+mod gdt;
+mod exceptions;
+mod pic;
+mod apic;
+mod irq;
+
+use spin::Mutex;
+use cga_screen::{CGAScreen, COLUMNS};
+use memory::{FrameAllocator, PageTable, STACK_ALLOCATOR};
+use self::idt::{Idt, ENTRY_COUNT};
+use self::gdt::{KERNEL_CODE_SELECTOR, DOUBLE_FAULT_IST_INDEX, NMI_IST_INDEX};
+
+pub const EXCEPTION_COUNT: u64 = 32;
+const IRQ_BASE: u64 = pic::MASTER_OFFSET as u64;
+const IRQ_END: u64 = IRQ_BASE + pic::IRQ_COUNT as u64;
+const APIC_SPURIOUS: u64 = apic::SPURIOUS_INTERRUPT as u64;
+const INTERRUPT_FLAG: u64 = 1 << 9;
+const NMI: usize = 2;
+const DOUBLE_FAULT: usize = 8;
+const IST_STACK_PAGES: usize = 4;
+
+static IDT: Mutex<Idt> = Mutex::new(Idt::new());
+
+extern {
+    // addresses of the interrupt entry stubs, see boot/interrupts.asm
+    static isr_table: [u64; ENTRY_COUNT];
+}
+
+// registers saved by the entry stubs in boot/interrupts.asm
+#[derive(Debug)]
+#[repr(C)]
+pub struct InterruptFrame {
+    pub r15: u64, pub r14: u64, pub r13: u64, pub r12: u64,
+    pub r11: u64, pub r10: u64, pub r9: u64, pub r8: u64,
+    pub rbp: u64, pub rdi: u64, pub rsi: u64, pub rdx: u64,
+    pub rcx: u64, pub rbx: u64, pub rax: u64,
+    pub vector: u64,
+    // 0, if the cpu does not push an error code for this vector
+    pub error_code: u64,
+    // pushed by the cpu
+    pub rip: u64, pub cs: u64, pub rflags: u64, pub rsp: u64, pub ss: u64,
+}
+
+pub fn init() {
+    let mut idt = IDT.lock();
+    for vector in 0..ENTRY_COUNT {
+        let handler = unsafe { isr_table[vector] };
+        idt.entry_mut(vector).set_handler(handler, KERNEL_CODE_SELECTOR);
+    }
+    // the idt lives in a static, so it is never moved
+    unsafe { idt.load(); }
+
+    pic::init();
+}
+
+// loads the rust gdt with a tss, needs the memory management to allocate the ist stacks
+pub fn init_gdt<A>(page_table: &mut PageTable, allocator: &mut A) where A: FrameAllocator {
+    let mut stack_allocator = STACK_ALLOCATOR.lock();
+    let double_fault_stack = stack_allocator.alloc(IST_STACK_PAGES, page_table, allocator)
+        .expect("could not allocate double fault stack");
+    let nmi_stack = stack_allocator.alloc(IST_STACK_PAGES, page_table, allocator)
+        .expect("could not allocate nmi stack");
+
+    gdt::init(double_fault_stack.top(), nmi_stack.top());
+
+    // the tss is loaded now, so the ist can be used
+    let mut idt = IDT.lock();
+    idt.entry_mut(DOUBLE_FAULT).set_stack_index(DOUBLE_FAULT_IST_INDEX);
+    idt.entry_mut(NMI).set_stack_index(NMI_IST_INDEX);
+}
+
+// uses the local and io apic instead of the pic, if the cpu has one
+pub fn init_apic<A>(page_table: &mut PageTable, allocator: &mut A) where A: FrameAllocator {
+    let apic = without_interrupts(|| apic::init(page_table, allocator));
+    if apic {
+        irq::switch_to_apic();
+    }
+    info!("interrupt controller: {}", if apic { "apic" } else { "pic" });
+}
+
+pub fn enable() {
+    unsafe { asm!("sti" :::: "volatile"); }
+}
+
+pub fn disable() {
+    unsafe { asm!("cli" :::: "volatile"); }
+}
+
+pub fn are_enabled() -> bool {
+    let rflags: u64;
+    unsafe { asm!("pushfq; popq $0" : "=r" (rflags) ::: "volatile"); }
+    (rflags & INTERRUPT_FLAG) != 0
+}
+
+// runs f with interrupts disabled and restores the previous state afterwards
+pub fn without_interrupts<F, R>(f: F) -> R where F: FnOnce() -> R {
+    let enabled = are_enabled();
+    disable();
+    let result = f();
+    if enabled {
+        enable();
+    }
+    result
+}
+
+#[no_mangle]
+pub extern "C" fn interrupt_dispatch(frame: &mut InterruptFrame) {
+    if frame.vector < EXCEPTION_COUNT {
+        exceptions::handle(frame);
+    } else if frame.vector < IRQ_END {
+        irq::dispatch((frame.vector - IRQ_BASE) as usize);
+    } else if frame.vector == APIC_SPURIOUS {
+        // must not be acknowledged
+    } else {
+        // DBG might be locked by the interrupted code, so its area is written directly
+        let mut dbg_area = CGAScreen::new(0, 0, COLUMNS, 2);
+        dbg_area.clear();
+        println!(dbg_area, "unexpected interrupt {}", frame.vector);
+    }
+}
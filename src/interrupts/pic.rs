@@ -0,0 +1,97 @@
+use io_port::IOPort;
+
+static MASTER_COMMAND: IOPort = IOPort::new(0x20);
+static MASTER_DATA: IOPort = IOPort::new(0x21);
+static SLAVE_COMMAND: IOPort = IOPort::new(0xa0);
+static SLAVE_DATA: IOPort = IOPort::new(0xa1);
+// writing to an unused port gives the pic time to process a command
+static WAIT_PORT: IOPort = IOPort::new(0x80);
+
+const ICW1_INIT: u8 = 0x11;
+const ICW4_8086: u8 = 0x01;
+const EOI: u8 = 0x20;
+const READ_ISR: u8 = 0x0b;
+// the slave is connected to irq 2 of the master
+const CASCADE_IRQ: usize = 2;
+
+pub const IRQ_COUNT: usize = 16;
+// the irqs are remapped directly above the cpu exceptions
+pub const MASTER_OFFSET: u8 = 32;
+pub const SLAVE_OFFSET: u8 = MASTER_OFFSET + 8;
+
+pub fn init() {
+    let wait = || WAIT_PORT.outb(0);
+
+    MASTER_COMMAND.outb(ICW1_INIT);
+    wait();
+    SLAVE_COMMAND.outb(ICW1_INIT);
+    wait();
+    MASTER_DATA.outb(MASTER_OFFSET);
+    wait();
+    SLAVE_DATA.outb(SLAVE_OFFSET);
+    wait();
+    MASTER_DATA.outb(1 << CASCADE_IRQ);
+    wait();
+    SLAVE_DATA.outb(CASCADE_IRQ as u8);
+    wait();
+    MASTER_DATA.outb(ICW4_8086);
+    wait();
+    SLAVE_DATA.outb(ICW4_8086);
+    wait();
+
+    // every irq is masked, until a handler is registered
+    MASTER_DATA.outb(!(1 << CASCADE_IRQ));
+    SLAVE_DATA.outb(0xff);
+}
+
+// masks all irqs, used when the apic takes over
+pub fn disable() {
+    MASTER_DATA.outb(0xff);
+    SLAVE_DATA.outb(0xff);
+}
+
+// must be called with interrupts disabled
+pub fn mask(irq: usize) {
+    assert!(irq < IRQ_COUNT);
+    let port = data_port(irq);
+    port.outb(port.inb() | (1 << (irq % 8)));
+}
+
+// must be called with interrupts disabled
+pub fn unmask(irq: usize) {
+    assert!(irq < IRQ_COUNT);
+    let port = data_port(irq);
+    port.outb(port.inb() & !(1 << (irq % 8)));
+}
+
+pub fn eoi(irq: usize) {
+    if irq >= 8 {
+        SLAVE_COMMAND.outb(EOI);
+    }
+    MASTER_COMMAND.outb(EOI);
+}
+
+// irq 7 and 15 are raised without being in service, if an interrupt vanishes too early
+pub fn is_spurious(irq: usize) -> bool {
+    match irq {
+        7 => (in_service(&MASTER_COMMAND) & 0x80) == 0,
+        15 => {
+            let spurious = (in_service(&SLAVE_COMMAND) & 0x80) == 0;
+            if spurious {
+                // the master does not know, that the irq was spurious
+                MASTER_COMMAND.outb(EOI);
+            }
+            spurious
+        }
+        _ => false,
+    }
+}
+
+fn in_service(command: &IOPort) -> u8 {
+    command.outb(READ_ISR);
+    command.inb()
+}
+
+fn data_port(irq: usize) -> &'static IOPort {
+    if irq < 8 { &MASTER_DATA } else { &SLAVE_DATA }
+}
@@ -0,0 +1,61 @@
+use spin::Mutex;
+use power;
+use super::{Key, KeyCode, Modifiers, SHIFT, CTRL, ALT, ALT_GR};
+
+const MAX_HOTKEYS: usize = 16;
+
+#[derive(Copy, Clone)]
+struct Hotkey {
+    modifiers: Modifiers,
+    code: KeyCode,
+    callback: fn(),
+}
+
+static HOTKEYS: Mutex<[Option<Hotkey>; MAX_HOTKEYS]> = Mutex::new([None; MAX_HOTKEYS]);
+
+pub fn register_defaults() {
+    register_hotkey(CTRL | ALT, KeyCode::Delete, power::reboot)
+        .expect("no free hotkey");
+    register_hotkey(CTRL | ALT, KeyCode::Escape, power::shutdown)
+        .expect("no free hotkey");
+}
+
+// the callback runs in the context of the reader of the keyboard, the keyboard is locked
+// returns the id needed to unregister the hotkey or None, if the table is full
+pub fn register_hotkey(modifiers: Modifiers, code: KeyCode, callback: fn()) -> Option<usize> {
+    let mut hotkeys = HOTKEYS.lock();
+    let id = hotkeys.iter().position(|hotkey| hotkey.is_none());
+    if let Some(id) = id {
+        hotkeys[id] = Some(Hotkey {modifiers: modifiers, code: code, callback: callback});
+    }
+    id
+}
+
+#[allow(dead_code)]
+pub fn unregister_hotkey(id: usize) {
+    let mut hotkeys = HOTKEYS.lock();
+    assert!(hotkeys[id].is_some(), "hotkey {} is not registered", id);
+    hotkeys[id] = None;
+}
+
+// returns true, if the key was consumed by a hotkey
+pub fn handle(key: &Key) -> bool {
+    // lock keys do not matter
+    let modifiers = key.modifiers() & (SHIFT | CTRL | ALT | ALT_GR);
+    let callback = {
+        let hotkeys = HOTKEYS.lock();
+        hotkeys.iter()
+            .filter_map(|hotkey| *hotkey)
+            .find(|hotkey| hotkey.modifiers == modifiers && hotkey.code == key.code())
+            .map(|hotkey| hotkey.callback)
+    };
+
+    // the callback may register or unregister hotkeys itself
+    match callback {
+        Some(callback) => {
+            callback();
+            true
+        }
+        None => false,
+    }
+}
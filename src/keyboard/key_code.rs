@@ -0,0 +1,86 @@
+// keys, which are identified by their position and not by the keymap
+#[derive(Copy, Clone, PartialEq, Eq, Debug)]
+pub enum KeyCode {
+    Char(char),
+    Escape, Backspace, Tab, Enter,
+    Up, Down, Left, Right,
+    Home, End, PageUp, PageDown,
+    Insert, Delete,
+    F1, F2, F3, F4, F5, F6, F7, F8, F9, F10, F11, F12,
+    Pause,
+    // a key without a character in the current keymap
+    Unknown,
+}
+
+impl KeyCode {
+    // returns None, if the character has to be looked up in the keymap
+    pub fn from_scancode(code: u8, extended: bool, num_lock: bool) -> Option<KeyCode> {
+        use self::KeyCode::*;
+
+        if extended {
+            return match code {
+                28 => Some(Enter),
+                53 => Some(Char('/')),
+                71 => Some(Home),
+                72 => Some(Up),
+                73 => Some(PageUp),
+                75 => Some(Left),
+                77 => Some(Right),
+                79 => Some(End),
+                80 => Some(Down),
+                81 => Some(PageDown),
+                82 => Some(Insert),
+                83 => Some(Delete),
+                _ => Some(Unknown),
+            };
+        }
+
+        let key_code = match code {
+            1 => Escape,
+            14 => Backspace,
+            15 => Tab,
+            28 => Enter,
+            59 => F1, 60 => F2, 61 => F3, 62 => F4, 63 => F5,
+            64 => F6, 65 => F7, 66 => F8, 67 => F9, 68 => F10,
+            87 => F11, 88 => F12,
+            // keypad
+            55 => Char('*'),
+            74 => Char('-'),
+            78 => Char('+'),
+            71...83 if num_lock => Char(KEYPAD_NUM_LOCK[(code - 71) as usize]),
+            71 => Home,
+            72 => Up,
+            73 => PageUp,
+            75 => Left,
+            77 => Right,
+            79 => End,
+            80 => Down,
+            81 => PageDown,
+            82 => Insert,
+            83 => Delete,
+            76 => Unknown,
+            _ => return None,
+        };
+        Some(key_code)
+    }
+
+    // the control character, that is commonly associated with the key
+    pub fn character(&self) -> char {
+        match *self {
+            KeyCode::Char(c) => c,
+            KeyCode::Escape => '\x1b',
+            KeyCode::Backspace => '\x08',
+            KeyCode::Tab => '\t',
+            KeyCode::Enter => '\n',
+            _ => '\0',
+        }
+    }
+}
+
+// scancodes 71 to 83, the entries for '-' and '+' are never used
+const KEYPAD_NUM_LOCK: [char; 13] = [
+    '7', '8', '9', '-',
+    '4', '5', '6', '+',
+    '1', '2', '3',
+    '0', '.',
+];
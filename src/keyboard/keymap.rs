@@ -0,0 +1,105 @@
+// maps scancodes (set 1) to characters, '\0' means that the key has no character
+pub struct Keymap {
+    name: &'static str,
+    normal: [char; SCANCODES],
+    shift: [char; SCANCODES],
+    // without an AltGr layer the right alt key is a normal alt key
+    has_alt_gr: bool,
+    alt_gr: [char; SCANCODES],
+}
+
+const SCANCODES: usize = 89;
+const N: char = '\0';
+
+pub static GERMAN: Keymap = Keymap {
+    name: "de",
+    normal: [
+        N, N, '1', '2', '3', '4', '5', '6', '7', '8', '9', '0', 'ß', '\'', N,
+        N, 'q', 'w', 'e', 'r', 't', 'z', 'u', 'i', 'o', 'p', 'ü', '+', '\n',
+        N, 'a', 's', 'd', 'f', 'g', 'h', 'j', 'k', 'l', 'ö', 'ä', '^', N, '#',
+        'y', 'x', 'c', 'v', 'b', 'n', 'm', ',', '.', '-', N,
+        '*', N, ' ', N, N, N, N, N, N, N, N, N, N, N, N, N, N, N, N, '-',
+        N, N, N, '+', N, N, N, N, N, N, N, '<', N, N
+    ],
+    shift: [
+        N, N, '!', '"', '§', '$', '%', '&', '/', '(', ')', '=', '?', '`', N,
+        N, 'Q', 'W', 'E', 'R', 'T', 'Z', 'U', 'I', 'O', 'P', 'Ü', '*', N,
+        N, 'A', 'S', 'D', 'F', 'G', 'H', 'J', 'K', 'L', 'Ö', 'Ä', '°', N, '\'',
+        'Y', 'X', 'C', 'V', 'B', 'N', 'M', ';', ':', '_', N,
+        N, N, ' ', N, N, N, N, N, N, N, N, N, N, N, N, N, N, N, N, N,
+        N, N, N, N, N, N, N, N, N, N, N, '>', N, N
+    ],
+    has_alt_gr: true,
+    alt_gr: [
+        N, N, N, '²', N, N, N, N, '{', '[', ']', '}', '\\', N, N,
+        N, '@', N, N, N, N, N, N, N, N, N, N, '~', N,
+        N, N, N, N, N, N, N, N, N, N, N, N, N, N, N,
+        N, N, N, N, N, N, 'µ', N, N, N, N,
+        N, N, ' ', N, N, N, N, N, N, N, N, N, N, N, N, N, N, N, N, N,
+        N, N, N, N, N, N, N, N, N, N, N, '|', N, N
+    ],
+};
+
+pub static US: Keymap = Keymap {
+    name: "us",
+    normal: [
+        N, N, '1', '2', '3', '4', '5', '6', '7', '8', '9', '0', '-', '=', N,
+        N, 'q', 'w', 'e', 'r', 't', 'y', 'u', 'i', 'o', 'p', '[', ']', '\n',
+        N, 'a', 's', 'd', 'f', 'g', 'h', 'j', 'k', 'l', ';', '\'', '`', N, '\\',
+        'z', 'x', 'c', 'v', 'b', 'n', 'm', ',', '.', '/', N,
+        '*', N, ' ', N, N, N, N, N, N, N, N, N, N, N, N, N, N, N, N, '-',
+        N, N, N, '+', N, N, N, N, N, N, N, '\\', N, N
+    ],
+    shift: [
+        N, N, '!', '@', '#', '$', '%', '^', '&', '*', '(', ')', '_', '+', N,
+        N, 'Q', 'W', 'E', 'R', 'T', 'Y', 'U', 'I', 'O', 'P', '{', '}', N,
+        N, 'A', 'S', 'D', 'F', 'G', 'H', 'J', 'K', 'L', ':', '"', '~', N, '|',
+        'Z', 'X', 'C', 'V', 'B', 'N', 'M', '<', '>', '?', N,
+        N, N, ' ', N, N, N, N, N, N, N, N, N, N, N, N, N, N, N, N, N,
+        N, N, N, N, N, N, N, N, N, N, N, '|', N, N
+    ],
+    has_alt_gr: false,
+    alt_gr: [
+        N, N, N, N, N, N, N, N, N, N, N, N, N, N, N,
+        N, N, N, N, N, N, N, N, N, N, N, N, N, N,
+        N, N, N, N, N, N, N, N, N, N, N, N, N, N, N,
+        N, N, N, N, N, N, N, N, N, N, N,
+        N, N, ' ', N, N, N, N, N, N, N, N, N, N, N, N, N, N, N, N, N,
+        N, N, N, N, N, N, N, N, N, N, N, N, N, N
+    ],
+};
+
+static KEYMAPS: [&'static Keymap; 2] = [&GERMAN, &US];
+
+impl Keymap {
+    pub fn by_name(name: &str) -> Option<&'static Keymap> {
+        KEYMAPS.iter().find(|keymap| keymap.name == name).map(|keymap| *keymap)
+    }
+
+    pub fn name(&self) -> &'static str { self.name }
+
+    pub fn has_alt_gr(&self) -> bool { self.has_alt_gr }
+
+    pub fn character(&self, code: u8, shift: bool, alt_gr: bool) -> char {
+        let code = code as usize;
+        if code >= SCANCODES {
+            return N;
+        }
+
+        if alt_gr {
+            self.alt_gr[code]
+        } else if shift {
+            self.shift[code]
+        } else {
+            self.normal[code]
+        }
+    }
+
+    // caps lock only affects letters
+    pub fn is_letter(&self, code: u8) -> bool {
+        match self.character(code, false, false) {
+            'a'...'z' | 'ä' | 'ö' | 'ü' => true,
+            _ => false,
+        }
+    }
+}
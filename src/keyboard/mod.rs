@@ -0,0 +1,281 @@
+use spin::Mutex;
+use interrupts;
+use ring_buffer::RingBuffer;
+use ps2::{self, Device, OUTB, AUXB, KEYBOARD_PORT};
+
+pub use self::keymap::Keymap;
+pub use self::key_code::KeyCode;
+#[allow(unused_imports)]
+pub use self::hotkeys::{register_hotkey, unregister_hotkey};
+
+mod keymap;
+mod key_code;
+mod hotkeys;
+
+const SET_SPEED: u8 = 0xf3;
+const SET_LEDS: u8 = 0xed;
+const BREAK_BIT: u8 = 0x80;
+const PREFIX1: u8 = 0xe0;
+const PREFIX2: u8 = 0xe1;
+const KEYBOARD_IRQ: usize = 1;
+
+bitflags! {
+    pub flags Modifiers: u8 {
+        const SHIFT =  1 << 0,
+        const CTRL =   1 << 1,
+        const ALT =    1 << 2,
+        const ALT_GR = 1 << 3,
+        const CAPS_LOCK =   1 << 4,
+        const NUM_LOCK =    1 << 5,
+        const SCROLL_LOCK = 1 << 6,
+    }
+}
+
+bitflags! {
+    pub flags Leds: u8 {
+        const SCROLL_LOCK_LED = 1 << 0,
+        const NUM_LOCK_LED =    1 << 1,
+        const CAPS_LOCK_LED =   1 << 2,
+    }
+}
+
+// Modifiers::empty() is no const fn
+const NO_MODIFIERS: Modifiers = Modifiers { bits: 0 };
+
+pub static KEYBOARD: Mutex<Keyboard> = Mutex::new(Keyboard {initialized: false, prefix: 0, gather: Key::invalid(),
+                                                            held_locks: NO_MODIFIERS,
+                                                            keymap: &keymap::GERMAN});
+
+// filled by the interrupt handler, emptied by the decoder
+static SCANCODES: RingBuffer<u8> = RingBuffer::new(0);
+
+fn keyboard_interrupt() {
+    let status = ps2::status();
+    // values from the mouse are left for the mouse interrupt handler
+    if (status & OUTB) != 0 && (status & AUXB) == 0 {
+        receive_scancode(ps2::read_data());
+    }
+}
+
+// also used for scancodes, which arrive while a driver waits for a response,
+// irq 1 must be masked or interrupts disabled then, so that the handler can not push at the same time
+pub fn receive_scancode(code: u8) {
+    // if the buffer is full, the key is lost
+    SCANCODES.push(code);
+}
+
+pub struct Keyboard {
+    initialized: bool,
+    gather: Key,
+    prefix: u8,
+    // lock keys, which are pressed right now, so that repeated make codes
+    // do not toggle the lock again
+    held_locks: Modifiers,
+    keymap: &'static Keymap,
+}
+
+impl Keyboard {
+
+    // returns false, if the ps2 controller has no working keyboard port
+    pub fn init(&mut self) -> bool {
+        assert!(!self.initialized);
+        if !ps2::ports().contains(KEYBOARD_PORT) {
+            return false;
+        }
+
+        self.set_repeat_rate(0, 0);
+        self.set_leds(Leds::empty());
+        hotkeys::register_defaults();
+        interrupts::register_irq(KEYBOARD_IRQ, keyboard_interrupt);
+
+        self.initialized = true;
+        true
+    }
+
+    // returns None, if no complete key is in the buffer
+    pub fn try_read_key(&mut self) -> Option<Key> {
+        assert!(self.initialized);
+
+        while let Some(code) = SCANCODES.pop() {
+            if self.key_decoded(code) && !hotkeys::handle(&self.gather) {
+                return Some(self.gather);
+            }
+        }
+        None
+    }
+
+    // halts the cpu until a key is pressed
+    pub fn read_key(&mut self) -> Key {
+        assert!(interrupts::are_enabled(), "read_key would wait forever");
+
+        loop {
+            // the check and the hlt must be atomic, otherwise the wakeup could get lost
+            interrupts::disable();
+            if let Some(key) = self.try_read_key() {
+                interrupts::enable();
+                return key;
+            }
+            // sti only takes effect after the next instruction
+            unsafe { asm!("sti; hlt" :::: "volatile"); }
+        }
+    }
+
+    pub fn set_keymap(&mut self, keymap: &'static Keymap) {
+        self.keymap = keymap;
+    }
+
+    pub fn keymap(&self) -> &'static Keymap { self.keymap }
+
+    // the right alt key is AltGr, if the keymap has such a layer
+    fn right_alt(&self) -> Modifiers {
+        if self.keymap.has_alt_gr() { ALT_GR } else { ALT }
+    }
+
+    pub fn set_leds(&mut self, leds: Leds) {
+        self.send_command(SET_LEDS, leds.bits());
+    }
+
+    fn key_decoded(&mut self, code: u8) -> bool {
+        if code == PREFIX1 || code == PREFIX2 {
+            self.prefix = code;
+            return false;
+        }
+
+        // pause sends ctrl together with PREFIX2, which is not a real ctrl
+        if self.prefix == PREFIX2 && (code & !BREAK_BIT) == 29 {
+            return false;
+        }
+        // some keys send shift together with PREFIX1, which is not a real shift
+        if self.prefix == PREFIX1 && ((code & !BREAK_BIT) == 42 || (code & !BREAK_BIT) == 54) {
+            self.prefix = 0;
+            return false;
+        }
+
+        let right_alt = self.right_alt();
+        if (code & BREAK_BIT) != 0 {
+            // release held modifiers
+            match code & !BREAK_BIT {
+                42 | 54 => self.gather.modifiers.remove(SHIFT),
+                56 if self.prefix == PREFIX1 => self.gather.modifiers.remove(right_alt),
+                56 => self.gather.modifiers.remove(ALT),
+                29 => self.gather.modifiers.remove(CTRL),
+                58 => self.held_locks.remove(CAPS_LOCK),
+                69 if self.prefix != PREFIX2 => self.held_locks.remove(NUM_LOCK),
+                70 => self.held_locks.remove(SCROLL_LOCK),
+                _ => {}
+            }
+            self.prefix = 0;
+            return false;
+        }
+
+        let mut done = false;
+        match code {
+            42 | 54 => self.gather.modifiers.insert(SHIFT),
+            56 if self.prefix == PREFIX1 => self.gather.modifiers.insert(right_alt),
+            56 => self.gather.modifiers.insert(ALT),
+            29 => self.gather.modifiers.insert(CTRL),
+            58 => self.toggle_lock(CAPS_LOCK),
+            // with PREFIX1 it is ctrl + break
+            70 if self.prefix != PREFIX1 => self.toggle_lock(SCROLL_LOCK),
+            70 => {}
+            // with PREFIX2 it is pause
+            69 if self.prefix != PREFIX2 => self.toggle_lock(NUM_LOCK),
+            69 => {
+                self.gather.set_code(KeyCode::Pause);
+                done = true;
+            }
+            _ => {
+                self.compute_key(code);
+                done = true;
+            }
+        }
+
+        self.prefix = 0;
+        done
+    }
+
+    fn toggle_lock(&mut self, lock: Modifiers) {
+        if self.held_locks.contains(lock) {
+            return;
+        }
+        self.held_locks.insert(lock);
+        self.gather.modifiers.toggle(lock);
+
+        let modifiers = self.gather.modifiers;
+        let mut leds = Leds::empty();
+        if modifiers.contains(CAPS_LOCK) { leds.insert(CAPS_LOCK_LED); }
+        if modifiers.contains(NUM_LOCK) { leds.insert(NUM_LOCK_LED); }
+        if modifiers.contains(SCROLL_LOCK) { leds.insert(SCROLL_LOCK_LED); }
+        self.set_leds(leds);
+    }
+
+    fn compute_key(&mut self, code: u8) {
+        let modifiers = self.gather.modifiers;
+        let extended = self.prefix == PREFIX1;
+        if let Some(key_code) = KeyCode::from_scancode(code, extended, modifiers.contains(NUM_LOCK)) {
+            self.gather.set_code(key_code);
+            return;
+        }
+
+        let caps = self.keymap.is_letter(code) && modifiers.contains(CAPS_LOCK);
+        let shift = modifiers.contains(SHIFT) != caps;
+        let key_code = match self.keymap.character(code, shift, modifiers.contains(ALT_GR)) {
+            '\0' => KeyCode::Unknown,
+            c => KeyCode::Char(c),
+        };
+        self.gather.set_code(key_code);
+    }
+
+    fn set_repeat_rate(&mut self, speed: u8, delay: u8) {
+        assert!(speed <= 3);
+        assert!(delay <= 31);
+        self.send_command(SET_SPEED, speed | (delay << 4));
+    }
+
+    fn send_command(&mut self, cmd: u8, data: u8) {
+        // the responses must not be consumed by the interrupt handler
+        interrupts::without_interrupts(|| {
+            if ps2::send_to_device(Device::Keyboard, cmd) {
+                ps2::send_to_device(Device::Keyboard, data);
+            }
+        });
+    }
+}
+
+
+#[derive(Copy, Clone)]
+pub struct Key {
+    valid: bool,
+    code: KeyCode,
+    modifiers: Modifiers,
+}
+
+impl Key {
+    pub const fn invalid() -> Key { Key {valid: false, code: KeyCode::Unknown, modifiers: NO_MODIFIERS} }
+
+    pub fn valid(&self) -> bool { self.valid }
+
+    pub fn modifiers(&self) -> Modifiers { self.modifiers }
+    pub fn shift(&self) -> bool { self.modifiers.contains(SHIFT) }
+    pub fn ctrl(&self) -> bool { self.modifiers.contains(CTRL) }
+    pub fn alt(&self) -> bool { self.modifiers.contains(ALT) }
+    pub fn alt_gr(&self) -> bool { self.modifiers.contains(ALT_GR) }
+    pub fn caps_lock(&self) -> bool { self.modifiers.contains(CAPS_LOCK) }
+    pub fn num_lock(&self) -> bool { self.modifiers.contains(NUM_LOCK) }
+    pub fn scroll_lock(&self) -> bool { self.modifiers.contains(SCROLL_LOCK) }
+
+    pub fn code(&self) -> KeyCode {
+        assert!(self.valid);
+        self.code
+    }
+
+    // '\0' for keys without a character, e.g. the arrow keys
+    pub fn character(&self) -> char {
+        self.code().character()
+    }
+
+    fn set_code(&mut self, code: KeyCode) {
+        self.valid = true;
+        self.code = code;
+    }
+}
@@ -1,4 +1,4 @@
-#![feature(lang_items, const_fn, asm, unique)]
+#![feature(lang_items, const_fn, asm, ptr_internals, alloc, alloc_error_handler, panic_info_message)]
 #![no_std]
 
 extern crate rlibc;
@@ -7,46 +7,89 @@ extern crate multiboot2;
 #[macro_use]
 extern crate bitflags;
 extern crate x86;
+extern crate alloc;
 
 #[macro_use]
 mod cga_screen;
+#[macro_use]
+mod serial;
+#[macro_use]
+mod log;
 mod io_port;
 mod keyboard;
+mod mouse;
+mod ps2;
 mod power;
 mod misc;
 mod memory;
+mod interrupts;
+mod ring_buffer;
+mod cmdline;
 
 use cga_screen::{SCREEN, CGAScreen, ROWS, COLUMNS};
-use keyboard::{KEYBOARD};
+use keyboard::{KEYBOARD, Keymap, KeyCode};
+use mouse::MOUSE;
 use misc::windows;
 use memory::FrameAllocator;
 use memory::PAGE_TABLE;
 
 use core::fmt;
 use core::fmt::Write;
+use core::alloc::Layout;
+use core::panic::PanicInfo;
+use alloc::boxed::Box;
+use alloc::vec::Vec;
 
 #[no_mangle]
 pub extern fn rust_main(multiboot_info_address: usize) {
     let multiboot_info = unsafe {multiboot2::load(multiboot_info_address)};
+    cmdline::init(multiboot_info);
 
     {
         let mut whole_screen = CGAScreen::new(0, 0, COLUMNS, ROWS);
         whole_screen.clear();
     }
 
-    let mut screen = SCREEN.lock();
     let mut keyboard = KEYBOARD.lock();
     let mut page_table = PAGE_TABLE.lock();
+    let mut mouse = MOUSE.lock();
 
-    keyboard.init();
-    screen.clear();
+    interrupts::init();
+    serial::COM1.lock().init(115200);
+    log::init();
+    let ps2_ports = ps2::init();
+    let keyboard_found = keyboard.init();
+    {
+        let mut screen = SCREEN.lock();
+        screen.clear();
+        // underline cursor
+        screen.set_cursor_shape(14, 15);
+        screen.show_cursor();
+    }
+    info!("ps2 ports: {:?}", ps2_ports);
+    if !keyboard_found {
+        error!("no keyboard found");
+    }
+
+    if let Some(name) = cmdline::option("keymap") {
+        match Keymap::by_name(name) {
+            Some(keymap) => keyboard.set_keymap(keymap),
+            None => error!("unknown keymap: {}", name),
+        }
+    }
+    info!("keymap: {}", keyboard.keymap().name());
+
+    if mouse.init() {
+        info!("mouse: wheel {}", mouse.has_wheel());
+    } else {
+        warn!("no mouse found");
+    }
 
     let memory_map_tag = multiboot_info.memory_map_tag()
         .expect("expected memory map tag");
-    println!(screen, "memory areas:");
+    info!("memory areas:");
     for area in memory_map_tag.memory_areas() {
-        println!(screen, "    start {:#x}, length: {:#x}",
-                 area.base_addr, area.length);
+        info!("    start {:#x}, length: {:#x}", area.base_addr, area.length);
     }
     let elf_sections_tag = multiboot_info.elf_sections_tag()
         .expect("Elf-sections tag required");
@@ -55,23 +98,55 @@ pub extern fn rust_main(multiboot_info_address: usize) {
     let multiboot_start = multiboot_info_address;
     let multiboot_end = multiboot_info_address + (multiboot_info.total_size as usize);
 
-    println!(screen, "kernel: start {:#x}, end: {:#x}", kernel_start, kernel_end);
-    println!(screen, "multiboot: start {:#x}, end: {:#x}", multiboot_start, multiboot_end);
-    let mut allocator = memory::RangeAllocator::new(memory_map_tag.memory_areas(),
-                                            kernel_start as usize, kernel_end as usize,
-                                            multiboot_start, multiboot_end);
+    info!("kernel: start {:#x}, end: {:#x}", kernel_start, kernel_end);
+    info!("multiboot: start {:#x}, end: {:#x}", multiboot_start, multiboot_end);
+    let mut allocator = memory::BuddyAllocator::new(memory_map_tag.memory_areas(),
+                                                    kernel_start as usize, kernel_end as usize,
+                                                    multiboot_start, multiboot_end);
+    info!("free frames: {}", allocator.free_frames());
 
     let new_frame = allocator.alloc().unwrap();
-    println!(screen, "First Frame: {:?}", new_frame);
+    info!("First Frame: {:?}", new_frame);
+
+    memory::remap_the_kernel(&mut page_table, &mut allocator, multiboot_info);
+    info!("kernel remapped");
+
+    memory::test_paging(&mut SCREEN.lock(), &mut page_table, &mut allocator);
+    memory::test_temporary_page(&mut SCREEN.lock(), &mut page_table, &mut allocator);
+    memory::test_huge_page(&mut SCREEN.lock(), &mut page_table, &mut allocator);
+    memory::test_buddy(&mut SCREEN.lock(), &mut allocator);
+
+    memory::init_heap(&mut page_table, &mut allocator);
+    interrupts::init_gdt(&mut page_table, &mut allocator);
+    interrupts::init_apic(&mut page_table, &mut allocator);
+    let heap_box = Box::new(42);
+    let heap_vec: Vec<usize> = (0..10).collect();
+    info!("heap: box {}, vec {:?}", heap_box, heap_vec);
 
-    memory::test_paging(&mut screen, &mut page_table, &mut allocator);
+    memory::test_slab(&mut SCREEN.lock(), &mut page_table, &mut allocator);
+
+    // the breakpoint handler returns
+    unsafe { asm!("int3" :::: "volatile"); }
+
+    interrupts::enable();
+
+    if !keyboard_found {
+        // no key can arrive, but interrupts are still handled
+        loop { unsafe { asm!("hlt" :::: "volatile"); } }
+    }
 
     loop {
-        let key = keyboard.key_hit();
-        if !key.valid() {continue;}
-        println!(screen, "Key {}", key.ascii());
+        let key = keyboard.read_key();
+        // mouse events since the last key
+        while let Some(event) = mouse.try_read_event() {
+            println!(SCREEN.lock(), "Mouse {} {} {} {:?}", event.dx(), event.dy(), event.dz(), event.buttons());
+        }
+        match key.code() {
+            KeyCode::Char(c) => println!(SCREEN.lock(), "Key {}", c),
+            code => println!(SCREEN.lock(), "Key {:?}", code),
+        }
 
-        if key.ascii() == 'q' {
+        if key.character() == 'q' {
             power::shutdown();
             windows();
         }
@@ -109,15 +184,28 @@ impl fmt::Write for AssertWriter {
     }
 }
 
-#[lang = "panic_fmt"]
-#[no_mangle]
-pub extern fn rust_begin_panic(_msg: core::fmt::Arguments,
-                               _file: &'static str,
-                               _line: u32) -> ! {
+#[alloc_error_handler]
+fn rust_oom(layout: Layout) -> ! {
+    panic!("kernel heap exhausted, allocating {} bytes", layout.size());
+}
+
+#[panic_handler]
+fn rust_begin_panic(info: &PanicInfo) -> ! {
+    let (file, line) = info.location().map(|l| (l.file(), l.line())).unwrap_or(("", 0));
 
     let mut out = AssertWriter {line: 0, pos: 0};
-    out.write_fmt(_msg).unwrap();
-    write!(&mut out, "\nFile: {}\nLine: {}\n", _file, _line).unwrap();
+    if let Some(msg) = info.message() {
+        out.write_fmt(*msg).unwrap();
+    }
+    write!(&mut out, "\nFile: {}\nLine: {}\n", file, line).unwrap();
+
+    // the panic may have happened while COM1 was locked
+    if let Some(mut com1) = serial::COM1.try_lock() {
+        if let Some(msg) = info.message() {
+            write!(com1, "panic: {}\n", msg).unwrap();
+        }
+        write!(com1, "File: {}\nLine: {}\n", file, line).unwrap();
+    }
 
     // hang
     loop{}
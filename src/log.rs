@@ -0,0 +1,250 @@
+use core::fmt;
+use core::fmt::Write;
+use core::str;
+use core::cmp;
+use spin::Mutex;
+use cga_screen::SCREEN;
+use interrupts;
+use cmdline;
+
+const MAX_FILTERS: usize = 16;
+const MAX_SINKS: usize = 8;
+const MEMORY_LOG_SIZE: usize = 4096;
+
+#[derive(Copy, Clone, PartialEq, Eq, PartialOrd, Ord, Debug)]
+pub enum Level {
+    Error, Warn, Info, Debug, Trace,
+}
+
+impl Level {
+    fn from_name(name: &str) -> Option<Level> {
+        match name {
+            "error" => Some(Level::Error),
+            "warn" => Some(Level::Warn),
+            "info" => Some(Level::Info),
+            "debug" => Some(Level::Debug),
+            "trace" => Some(Level::Trace),
+            _ => None,
+        }
+    }
+
+    fn name(&self) -> &'static str {
+        match *self {
+            Level::Error => "ERROR",
+            Level::Warn => "WARN",
+            Level::Info => "INFO",
+            Level::Debug => "DEBUG",
+            Level::Trace => "TRACE",
+        }
+    }
+}
+
+// gets the level, the module path without the crate name and the message
+pub type Sink = fn(Level, &str, fmt::Arguments);
+
+macro_rules! log {
+    ($level:expr, $($arg:tt)*) => ({
+        $crate::log::log($level, module_path!(), format_args!($($arg)*));
+    });
+}
+
+macro_rules! error {
+    ($($arg:tt)*) => (log!($crate::log::Level::Error, $($arg)*));
+}
+
+macro_rules! warn {
+    ($($arg:tt)*) => (log!($crate::log::Level::Warn, $($arg)*));
+}
+
+macro_rules! info {
+    ($($arg:tt)*) => (log!($crate::log::Level::Info, $($arg)*));
+}
+
+#[allow(unused_macros)]
+macro_rules! debug {
+    ($($arg:tt)*) => (log!($crate::log::Level::Debug, $($arg)*));
+}
+
+#[allow(unused_macros)]
+macro_rules! trace {
+    ($($arg:tt)*) => (log!($crate::log::Level::Trace, $($arg)*));
+}
+
+struct Filters {
+    default: Level,
+    // the longest matching module prefix wins
+    modules: [Option<(&'static str, Level)>; MAX_FILTERS],
+}
+
+impl Filters {
+    fn level(&self, module: &str) -> Level {
+        let mut level = self.default;
+        let mut matched = 0;
+        for filter in self.modules.iter() {
+            if let Some((prefix, filter_level)) = *filter {
+                if prefix.len() > matched && is_in_module(module, prefix) {
+                    level = filter_level;
+                    matched = prefix.len();
+                }
+            }
+        }
+        level
+    }
+
+    fn set(&mut self, module: &'static str, level: Level) -> bool {
+        for filter in self.modules.iter_mut() {
+            let free = match *filter {
+                Some((prefix, _)) => prefix == module,
+                None => true,
+            };
+            if free {
+                *filter = Some((module, level));
+                return true;
+            }
+        }
+        false
+    }
+}
+
+// the oldest messages are overwritten, non ascii characters are replaced
+struct MemoryLog {
+    bytes: [u8; MEMORY_LOG_SIZE],
+    start: usize,
+    len: usize,
+}
+
+impl fmt::Write for MemoryLog {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for c in s.chars() {
+            let byte = if (c as u32) < 0x80 { c as u8 } else { b'?' };
+            let end = (self.start + self.len) % MEMORY_LOG_SIZE;
+            self.bytes[end] = byte;
+            if self.len < MEMORY_LOG_SIZE {
+                self.len += 1;
+            } else {
+                self.start = (self.start + 1) % MEMORY_LOG_SIZE;
+            }
+        }
+        Ok(())
+    }
+}
+
+// all locks are only taken with interrupts disabled
+static FILTERS: Mutex<Filters> = Mutex::new(Filters {default: Level::Info, modules: [None; MAX_FILTERS]});
+static SINKS: Mutex<[Option<Sink>; MAX_SINKS]> = Mutex::new([None; MAX_SINKS]);
+static MEMORY_LOG: Mutex<MemoryLog> = Mutex::new(MemoryLog {bytes: [0; MEMORY_LOG_SIZE], start: 0, len: 0});
+
+// the filters are read from the command line, e.g. "log=debug,memory=trace,keyboard=warn"
+pub fn init() {
+    add_sink(cga_sink).expect("no free log sink");
+    add_sink(serial_sink).expect("no free log sink");
+    add_sink(memory_sink).expect("no free log sink");
+
+    if let Some(spec) = cmdline::option("log") {
+        for part in spec.split(',') {
+            let mut parts = part.splitn(2, '=');
+            let (module, level) = match (parts.next(), parts.next()) {
+                (Some(level), None) => (None, level),
+                (Some(module), Some(level)) => (Some(module), level),
+                _ => continue,
+            };
+
+            match (module, Level::from_name(level)) {
+                (None, Some(level)) => set_default_level(level),
+                (Some(module), Some(level)) => {
+                    if !set_module_level(module, level) {
+                        warn!("too many log filters, ignoring {}", module);
+                    }
+                }
+                (_, None) => warn!("unknown log level {}", level),
+            }
+        }
+    }
+}
+
+pub fn log(level: Level, module: &str, args: fmt::Arguments) {
+    let module = strip_crate_name(module);
+    interrupts::without_interrupts(|| {
+        if level > FILTERS.lock().level(module) {
+            return;
+        }
+
+        // sinks may add or remove sinks themselves
+        let sinks = *SINKS.lock();
+        for sink in sinks.iter() {
+            if let Some(sink) = *sink {
+                sink(level, module, args);
+            }
+        }
+    });
+}
+
+pub fn set_default_level(level: Level) {
+    interrupts::without_interrupts(|| FILTERS.lock().default = level);
+}
+
+// module is the path without the crate name, e.g. "memory::paging",
+// returns false, if there are too many filters
+pub fn set_module_level(module: &'static str, level: Level) -> bool {
+    interrupts::without_interrupts(|| FILTERS.lock().set(module, level))
+}
+
+// returns the id needed to remove the sink or None, if there are too many sinks
+pub fn add_sink(sink: Sink) -> Option<usize> {
+    interrupts::without_interrupts(|| {
+        let mut sinks = SINKS.lock();
+        let id = sinks.iter().position(|sink| sink.is_none());
+        if let Some(id) = id {
+            sinks[id] = Some(sink);
+        }
+        id
+    })
+}
+
+#[allow(dead_code)]
+pub fn remove_sink(id: usize) {
+    interrupts::without_interrupts(|| {
+        let mut sinks = SINKS.lock();
+        assert!(sinks[id].is_some(), "log sink {} is not registered", id);
+        sinks[id] = None;
+    });
+}
+
+// writes the messages kept by the memory sink
+#[allow(dead_code)]
+pub fn replay_memory_log<W: fmt::Write>(out: &mut W) -> fmt::Result {
+    interrupts::without_interrupts(|| {
+        let log = MEMORY_LOG.lock();
+        let first = cmp::min(MEMORY_LOG_SIZE, log.start + log.len);
+        let second = log.len - (first - log.start);
+        // only ascii is stored
+        out.write_str(str::from_utf8(&log.bytes[log.start..first]).unwrap())?;
+        out.write_str(str::from_utf8(&log.bytes[..second]).unwrap())
+    })
+}
+
+// the code logging must not hold the lock of SCREEN
+pub fn cga_sink(level: Level, module: &str, args: fmt::Arguments) {
+    let mut screen = SCREEN.lock();
+    println!(screen, "[{} {}] {}", level.name(), module, args);
+}
+
+pub fn serial_sink(level: Level, module: &str, args: fmt::Arguments) {
+    serial_println!("[{} {}] {}", level.name(), module, args);
+}
+
+pub fn memory_sink(level: Level, module: &str, args: fmt::Arguments) {
+    write!(MEMORY_LOG.lock(), "[{} {}] {}\n", level.name(), module, args).unwrap();
+}
+
+fn strip_crate_name(module: &str) -> &str {
+    match module.find("::") {
+        Some(index) => &module[index + 2..],
+        None => module,
+    }
+}
+
+fn is_in_module(module: &str, prefix: &str) -> bool {
+    module.starts_with(prefix) &&
+        (module.len() == prefix.len() || module[prefix.len()..].starts_with("::"))
+}
@@ -0,0 +1,162 @@
+use core::sync::atomic::{AtomicBool, ATOMIC_BOOL_INIT, Ordering};
+use memory::{Frame, FrameAllocator, MAX_FRAMES, usable_frames};
+use multiboot2::{MemoryAreaIter};
+use cga_screen::CGAScreen;
+
+// blocks of order n consist of 2^n frames, the biggest blocks are 4MiB
+pub const MAX_ORDER: usize = 10;
+const BITS: usize = 64;
+// the bitmap of order n has MAX_FRAMES / 2^n bits, so all orders together
+// need less than twice the bits of order 0
+const STORAGE_WORDS: usize = 2 * MAX_FRAMES / BITS;
+
+// for every order there is a bitmap, a set bit means, that the block
+// with this index is free and not merged with its buddy
+static mut STORAGE: [u64; STORAGE_WORDS] = [0; STORAGE_WORDS];
+static STORAGE_TAKEN: AtomicBool = ATOMIC_BOOL_INIT;
+
+pub struct BuddyAllocator {
+    bitmaps: &'static mut [u64; STORAGE_WORDS],
+    free_blocks: [usize; MAX_ORDER + 1],
+    free_frames: usize,
+}
+
+impl BuddyAllocator {
+    pub fn new(areas: MemoryAreaIter,
+               kernel_start: usize, kernel_end: usize,
+               multiboot_start: usize, multiboot_end: usize) -> BuddyAllocator {
+        assert!(!STORAGE_TAKEN.swap(true, Ordering::SeqCst),
+                "there can only be one BuddyAllocator");
+
+        let mut allocator = BuddyAllocator {
+            bitmaps: unsafe { &mut STORAGE },
+            free_blocks: [0; MAX_ORDER + 1],
+            free_frames: 0,
+        };
+
+        // freeing every single frame merges them to the biggest possible blocks
+        usable_frames(areas, kernel_start, kernel_end, multiboot_start, multiboot_end,
+                      |frame| {
+                          if frame.number < MAX_FRAMES {
+                              allocator.free_contiguous(frame, 0);
+                          }
+                      });
+
+        allocator
+    }
+
+    pub fn free_frames(&self) -> usize { self.free_frames }
+
+    // returns the first of 2^order physically contiguous frames,
+    // the first frame is aligned to 2^order frames
+    pub fn alloc_contiguous(&mut self, order: usize) -> Option<Frame> {
+        assert!(order <= MAX_ORDER, "order {} is too big", order);
+
+        for current in order..MAX_ORDER + 1 {
+            if let Some(index) = self.find_free(current) {
+                self.clear_bit(current, index);
+
+                // split the block until it has the requested size
+                // and free the upper halves
+                let mut index = index;
+                for lower in (order..current).rev() {
+                    index *= 2;
+                    self.set_bit(lower, index + 1);
+                }
+
+                self.free_frames -= 1 << order;
+                return Some(Frame { number: index << order });
+            }
+        }
+        None
+    }
+
+    pub fn free_contiguous(&mut self, frame: Frame, order: usize) {
+        assert!(order <= MAX_ORDER, "order {} is too big", order);
+        assert!(frame.number < MAX_FRAMES, "{:?} was not allocated by this allocator", frame);
+        assert!(frame.number % (1 << order) == 0,
+                "{:?} is not aligned to a block of order {}", frame, order);
+        // neither a block containing the frames nor a block inside them may be free
+        for current in order..MAX_ORDER + 1 {
+            assert!(!self.is_set(current, frame.number >> current),
+                    "double free of {:?} with order {}", frame, order);
+        }
+        for lower in 0..order {
+            let first = frame.number >> lower;
+            for index in first..first + (1 << (order - lower)) {
+                assert!(!self.is_set(lower, index),
+                        "double free of {:?} with order {}", frame, order);
+            }
+        }
+
+        self.free_frames += 1 << order;
+
+        // merge with the buddy as long as it is free
+        let mut index = frame.number >> order;
+        let mut order = order;
+        while order < MAX_ORDER && self.is_set(order, index ^ 1) {
+            self.clear_bit(order, index ^ 1);
+            index /= 2;
+            order += 1;
+        }
+        self.set_bit(order, index);
+    }
+
+    fn find_free(&self, order: usize) -> Option<usize> {
+        if self.free_blocks[order] == 0 {
+            return None;
+        }
+
+        let offset = word_offset(order);
+        let words = (MAX_FRAMES >> order) / BITS;
+        for (i, word) in self.bitmaps[offset..offset + words].iter().enumerate() {
+            if *word != 0 {
+                return Some(i * BITS + word.trailing_zeros() as usize);
+            }
+        }
+        unreachable!();
+    }
+
+    fn is_set(&self, order: usize, index: usize) -> bool {
+        self.bitmaps[word_offset(order) + index / BITS] & (1 << (index % BITS)) != 0
+    }
+
+    fn set_bit(&mut self, order: usize, index: usize) {
+        self.bitmaps[word_offset(order) + index / BITS] |= 1 << (index % BITS);
+        self.free_blocks[order] += 1;
+    }
+
+    fn clear_bit(&mut self, order: usize, index: usize) {
+        self.bitmaps[word_offset(order) + index / BITS] &= !(1 << (index % BITS));
+        self.free_blocks[order] -= 1;
+    }
+}
+
+impl FrameAllocator for BuddyAllocator {
+    fn alloc(&mut self) -> Option<Frame> {
+        self.alloc_contiguous(0)
+    }
+
+    fn free(&mut self, frame: Frame) {
+        self.free_contiguous(frame, 0);
+    }
+}
+
+// index of the first word of the bitmap for this order
+fn word_offset(order: usize) -> usize {
+    (0..order).map(|o| (MAX_FRAMES >> o) / BITS).sum()
+}
+
+pub fn test_buddy(screen: &mut CGAScreen, allocator: &mut BuddyAllocator) {
+    let order = 4;
+    let free_frames = allocator.free_frames();
+
+    let block = allocator.alloc_contiguous(order).expect("no contiguous frames");
+    assert!(block.number % (1 << order) == 0, "{:?} is not aligned", block);
+    assert_eq!(allocator.free_frames(), free_frames - (1 << order));
+    println!(screen, "buddy block of order {}: {:?}", order, block);
+
+    allocator.free_contiguous(block, order);
+    assert_eq!(allocator.free_frames(), free_frames);
+    println!(screen, "buddy: {} free frames after the free", allocator.free_frames());
+}
@@ -0,0 +1,140 @@
+use core::alloc::{GlobalAlloc, Layout};
+use core::mem::{align_of};
+use core::ptr;
+use spin::Mutex;
+
+// a free region of the heap, which is stored at the beginning of the region itself
+struct Hole {
+    size: usize,
+    next: *mut Hole,
+}
+
+// size_of::<Hole>()
+const HOLE_SIZE: usize = 2 * 8;
+
+// first fit allocator, which keeps the free regions in a list sorted by address
+struct LinkedList {
+    // dummy hole with size 0, which is never part of the heap
+    head: Hole,
+}
+
+// the holes are only accessed through the mutex
+unsafe impl Send for LinkedList {}
+
+impl LinkedList {
+    const fn empty() -> LinkedList {
+        LinkedList { head: Hole { size: 0, next: 0 as *mut Hole } }
+    }
+
+    unsafe fn alloc(&mut self, size: usize, align: usize) -> Option<usize> {
+        let mut previous: *mut Hole = &mut self.head;
+
+        while !(*previous).next.is_null() {
+            let hole = (*previous).next;
+            let hole_start = hole as usize;
+            let hole_end = hole_start + (*hole).size;
+
+            let mut start = align_up(hole_start, align);
+            if start != hole_start && start - hole_start < HOLE_SIZE {
+                // the padding in front is too small to store a hole
+                start = align_up(hole_start + HOLE_SIZE, align);
+            }
+            let end = start + size;
+
+            if end > hole_end || (end != hole_end && hole_end - end < HOLE_SIZE) {
+                previous = hole;
+                continue;
+            }
+
+            // remove the hole and give back the unused parts
+            (*previous).next = (*hole).next;
+            if start != hole_start {
+                self.free(hole_start, start - hole_start);
+            }
+            if end != hole_end {
+                self.free(end, hole_end - end);
+            }
+            return Some(start);
+        }
+        None
+    }
+
+    unsafe fn free(&mut self, address: usize, size: usize) {
+        assert!(size >= HOLE_SIZE);
+        assert!(address % align_of::<Hole>() == 0);
+
+        let mut previous: *mut Hole = &mut self.head;
+        while !(*previous).next.is_null() && ((*previous).next as usize) < address {
+            previous = (*previous).next;
+        }
+
+        let next = (*previous).next;
+        assert!(next.is_null() || address + size <= next as usize, "double free on the heap");
+
+        let mut hole = address as *mut Hole;
+        ptr::write(hole, Hole { size: size, next: next });
+
+        // merge with the following hole
+        if !next.is_null() && address + size == next as usize {
+            (*hole).size += (*next).size;
+            (*hole).next = (*next).next;
+        }
+
+        // merge with the preceding hole, the head is no real hole
+        if previous != &mut self.head as *mut Hole {
+            assert!(previous as usize + (*previous).size <= address, "double free on the heap");
+            if previous as usize + (*previous).size == address {
+                (*previous).size += (*hole).size;
+                (*previous).next = (*hole).next;
+                hole = previous;
+            }
+        }
+
+        if hole != previous {
+            (*previous).next = hole;
+        }
+    }
+}
+
+pub struct HeapAllocator {
+    holes: Mutex<LinkedList>,
+}
+
+impl HeapAllocator {
+    pub const fn empty() -> HeapAllocator {
+        HeapAllocator { holes: Mutex::new(LinkedList::empty()) }
+    }
+
+    // the memory from start to start + size must be mapped and unused
+    pub unsafe fn init(&self, start: usize, size: usize) {
+        assert!(start % align_of::<Hole>() == 0);
+        self.holes.lock().free(start, size - size % HOLE_SIZE);
+    }
+}
+
+unsafe impl GlobalAlloc for HeapAllocator {
+    unsafe fn alloc(&self, layout: Layout) -> *mut u8 {
+        let (size, align) = hole_layout(&layout);
+        match self.holes.lock().alloc(size, align) {
+            Some(address) => address as *mut u8,
+            None => ptr::null_mut(),
+        }
+    }
+
+    unsafe fn dealloc(&self, ptr: *mut u8, layout: Layout) {
+        let (size, _) = hole_layout(&layout);
+        self.holes.lock().free(ptr as usize, size);
+    }
+}
+
+// every allocation must be able to hold a hole, when it is freed again
+fn hole_layout(layout: &Layout) -> (usize, usize) {
+    let size = align_up(layout.size(), HOLE_SIZE);
+    let align = if layout.align() > align_of::<Hole>() { layout.align() } else { align_of::<Hole>() };
+    (if size < HOLE_SIZE { HOLE_SIZE } else { size }, align)
+}
+
+fn align_up(address: usize, align: usize) -> usize {
+    assert!(align.is_power_of_two());
+    (address + align - 1) & !(align - 1)
+}
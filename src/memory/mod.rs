@@ -1,13 +1,56 @@
 pub use self::range_allocator::RangeAllocator;
-pub use self::paging::test_paging;
-pub use self::paging::PAGE_TABLE;
+pub use self::buddy_allocator::{BuddyAllocator, test_buddy};
+pub use self::paging::{test_paging, test_temporary_page, test_huge_page, remap_the_kernel};
+pub use self::paging::{PAGE_TABLE, PageTable};
+pub use self::heap_allocator::HeapAllocator;
+pub use self::slab::{SLAB_ALLOCATOR, test_slab};
+pub use self::stack_allocator::{STACK_ALLOCATOR, Stack, is_stack_guard};
 
 mod range_allocator;
+mod buddy_allocator;
+mod heap_allocator;
 mod paging;
+mod slab;
+mod stack_allocator;
+
+use multiboot2::{MemoryAreaIter};
+use self::paging::{Page, WRITABLE, NO_EXECUTE, NO_CACHE, WRITE_THROUGH};
 
 pub const FRAME_SIZE: usize = 4096;
 pub type PhysicalAddress = usize;
 
+// physical memory above 4GiB is ignored by the frame allocators
+pub const MAX_FRAMES: usize = (1 << 32) / FRAME_SIZE;
+
+// the heap starts at 1GiB, that is P3 entry 1 of the first P4 entry,
+// whose P3 entry 0 holds the identity mapped kernel
+pub const HEAP_START: usize = 0o_000_001_000_000_0000;
+pub const HEAP_SIZE: usize = 1024 * 1024;
+
+#[global_allocator]
+static HEAP_ALLOCATOR: HeapAllocator = HeapAllocator::empty();
+
+pub fn init_heap<A>(page_table: &mut PageTable, allocator: &mut A) where A: FrameAllocator {
+    let heap_start_page = Page::containing_address(HEAP_START);
+    let heap_end_page = Page::containing_address(HEAP_START + HEAP_SIZE - 1);
+
+    for page in Page::range_inclusive(heap_start_page, heap_end_page) {
+        page_table.map(page, WRITABLE | NO_EXECUTE, allocator);
+    }
+
+    unsafe { HEAP_ALLOCATOR.init(HEAP_START, HEAP_SIZE); }
+}
+
+// identity maps the page with the memory mapped registers of a device
+pub fn map_device_memory<A>(page_table: &mut PageTable, address: PhysicalAddress,
+                            allocator: &mut A) where A: FrameAllocator {
+    if page_table.translate(address).is_some() {
+        return;
+    }
+    page_table.identity_map(Frame::containing_address(address),
+                            WRITABLE | NO_CACHE | WRITE_THROUGH | NO_EXECUTE, allocator);
+}
+
 // represents a physical frame
 #[derive(Debug, PartialEq, Eq, PartialOrd, Ord)]
 pub struct Frame {
@@ -57,3 +100,31 @@ impl Iterator for FrameRangeInclusiveIter {
         Some(next)
     }
 }
+
+// calls f for every frame, which lies completely inside one of the available
+// memory areas and does not overlap the kernel or the multiboot information
+fn usable_frames<F>(areas: MemoryAreaIter,
+                    kernel_start: usize, kernel_end: usize,
+                    multiboot_start: usize, multiboot_end: usize,
+                    mut f: F) where F: FnMut(Frame) {
+    let overlaps = |frame: &Frame, start: usize, end: usize| {
+        frame.start_address() < end && frame.start_address() + FRAME_SIZE > start
+    };
+
+    for area in areas {
+        let area_start = area.base_addr as usize;
+        let area_end = (area.base_addr + area.length) as usize;
+        // only use frames, which are completely inside the area
+        let first = Frame { number: (area_start + FRAME_SIZE - 1) / FRAME_SIZE };
+        let last = Frame { number: area_end / FRAME_SIZE };
+
+        for frame in Frame::range_inclusive(first, last) {
+            if frame.start_address() + FRAME_SIZE > area_end ||
+                overlaps(&frame, kernel_start, kernel_end) ||
+                overlaps(&frame, multiboot_start, multiboot_end) {
+                continue;
+            }
+            f(frame);
+        }
+    }
+}
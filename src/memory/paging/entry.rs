@@ -1,4 +1,5 @@
 use memory::Frame;
+use multiboot2::ElfSection;
 
 bitflags! {
     pub flags EntryFlags: u64 {
@@ -18,6 +19,27 @@ bitflags! {
     }
 }
 
+impl EntryFlags {
+    pub fn from_elf_section_flags(section: &ElfSection) -> EntryFlags {
+        use multiboot2::{ELF_SECTION_ALLOCATED, ELF_SECTION_WRITABLE, ELF_SECTION_EXECUTABLE};
+
+        let mut flags = EntryFlags::empty();
+
+        if section.flags().contains(ELF_SECTION_ALLOCATED) {
+            // section is loaded to memory
+            flags.insert(PRESENT);
+        }
+        if section.flags().contains(ELF_SECTION_WRITABLE) {
+            flags.insert(WRITABLE);
+        }
+        if !section.flags().contains(ELF_SECTION_EXECUTABLE) {
+            flags.insert(NO_EXECUTE);
+        }
+
+        flags
+    }
+}
+
 #[derive(Clone, Copy)]
 pub struct Entry(u64);
 
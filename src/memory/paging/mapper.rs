@@ -5,10 +5,13 @@ use spin::Mutex;
 use x86::shared::tlb;
 use super::entry::*;
 use super::{Page, VirtualAddress, PAGE_SIZE};
-use super::table::{self, Table, Level4, Level1, ENTRY_COUNT};
+use super::temporary_page::TemporaryPage;
+use super::table::{self, Table, Level4, Level1, HierarchicalLevel, ENTRY_COUNT};
 use memory::{Frame, FrameAllocator, PhysicalAddress};
 
 const P4: *mut Table<Level4> = 0xffffffff_fffff000 as *mut _;
+// used to fill the new table, when a huge page is split, outside of the boot identity map
+const SPLIT_TEMPORARY_PAGE: VirtualAddress = 0xcafea000;
 
 pub struct Mapper {
     p4: Unique<Table<Level4>>
@@ -16,7 +19,7 @@ pub struct Mapper {
 
 impl Mapper {
     pub const unsafe fn new() -> Mapper {
-        Mapper { p4: Unique::new(P4) }
+        Mapper { p4: Unique::new_unchecked(P4) }
     }
 
     pub fn translate(&self, virtual_address: VirtualAddress) -> Option<PhysicalAddress> {
@@ -67,7 +70,7 @@ impl Mapper {
 
     pub fn map_to<A>(&mut self, page: Page, frame: Frame, flags: EntryFlags,
                      allocator: &mut A) where A : FrameAllocator {
-        assert!(!flags.contains(HUGE_PAGE), "HUGE pages are not supported for mapping");
+        assert!(!flags.contains(HUGE_PAGE), "use map_to_huge_2m or map_to_huge_1g for huge pages");
         let mut p3 = self.p4_mut().next_table_create(page.p4_index(), allocator);
         let mut p2 = p3.next_table_create(page.p3_index(), allocator);
         let mut p1 = p2.next_table_create(page.p2_index(), allocator);
@@ -87,24 +90,193 @@ impl Mapper {
         self.map_to(page, frame, flags, allocator);
     }
 
-    pub fn unmap<A>(&mut self, page: Page, allocator: &mut A)
+    // page and frame must be 2MiB aligned
+    pub fn map_to_huge_2m<A>(&mut self, page: Page, frame: Frame, flags: EntryFlags,
+                             allocator: &mut A) where A : FrameAllocator {
+        assert!(page.number % ENTRY_COUNT == 0, "{:?} is not 2MiB aligned", page);
+        assert!(frame.number % ENTRY_COUNT == 0, "{:?} is not 2MiB aligned", frame);
+        let mut p3 = self.p4_mut().next_table_create(page.p4_index(), allocator);
+        let mut p2 = p3.next_table_create(page.p3_index(), allocator);
+        assert!(p2[page.p2_index()].is_unused());
+        p2[page.p2_index()].set(frame, flags | PRESENT | HUGE_PAGE);
+    }
+
+    // page and frame must be 1GiB aligned
+    pub fn map_to_huge_1g<A>(&mut self, page: Page, frame: Frame, flags: EntryFlags,
+                             allocator: &mut A) where A : FrameAllocator {
+        assert!(page.number % (ENTRY_COUNT * ENTRY_COUNT) == 0, "{:?} is not 1GiB aligned", page);
+        assert!(frame.number % (ENTRY_COUNT * ENTRY_COUNT) == 0,
+                "{:?} is not 1GiB aligned", frame);
+        let mut p3 = self.p4_mut().next_table_create(page.p4_index(), allocator);
+        assert!(p3[page.p3_index()].is_unused());
+        p3[page.p3_index()].set(frame, flags | PRESENT | HUGE_PAGE);
+    }
+
+    // returns the first frame of the huge page, the frames are not freed,
+    // because huge pages like the boot identity map often do not belong to an allocator,
+    // only page tables, which became empty, are returned to the allocator
+    pub fn unmap_huge_2m<A>(&mut self, page: Page, allocator: &mut A) -> Frame
         where A: FrameAllocator
     {
-        assert!(self.translate(page.start_address()).is_some());
-
-        let p1 = self.p4_mut()
+        assert!(page.number % ENTRY_COUNT == 0, "{:?} is not 2MiB aligned", page);
+        let p2 = self.p4_mut()
             .next_table_mut(page.p4_index())
             .and_then(|p3| p3.next_table_mut(page.p3_index()))
-            .and_then(|p2| p2.next_table_mut(page.p2_index()))
-            .expect("mapping code does not support huge pages");
-        let frame = p1[page.p1_index()].frame().unwrap();
-        p1[page.p1_index()].set_unused();
-        // TODO free p(1,2,3) table if empty
+            .expect("page is not mapped by a 2MiB page");
+        assert!(p2[page.p2_index()].flags().contains(HUGE_PAGE), "page is not a 2MiB page");
+        let frame = p2[page.p2_index()].frame().unwrap();
+        p2[page.p2_index()].set_unused();
+
+        unsafe { tlb::flush(page.start_address()); }
+        self.free_empty_tables(page, allocator);
+        frame
+    }
+
+    // see unmap_huge_2m
+    pub fn unmap_huge_1g<A>(&mut self, page: Page, allocator: &mut A) -> Frame
+        where A: FrameAllocator
+    {
+        assert!(page.number % (ENTRY_COUNT * ENTRY_COUNT) == 0, "{:?} is not 1GiB aligned", page);
+        let p3 = self.p4_mut()
+            .next_table_mut(page.p4_index())
+            .expect("page is not mapped by a 1GiB page");
+        assert!(p3[page.p3_index()].flags().contains(HUGE_PAGE), "page is not a 1GiB page");
+        let frame = p3[page.p3_index()].frame().unwrap();
+        p3[page.p3_index()].set_unused();
 
         unsafe { tlb::flush(page.start_address()); }
+        self.free_empty_tables(page, allocator);
+        frame
+    }
+
+    // splits the huge pages containing page, until page is mapped by a normal 4KiB page,
+    // the huge page may contain the running code, must not be called inside PageTable::with
+    pub fn split_huge_page<A>(&mut self, page: Page, allocator: &mut A)
+        where A: FrameAllocator
+    {
+        let p3_entry = self.p4().next_table(page.p4_index())
+            .expect("page is not mapped")[page.p3_index()];
+        if p3_entry.flags().contains(HUGE_PAGE) {
+            let table_frame = self.create_split_table(p3_entry, ENTRY_COUNT, allocator);
+            let p3 = self.p4_mut().next_table_mut(page.p4_index()).unwrap();
+            install_split_table(p3, page.p3_index(), table_frame, p3_entry.flags());
+        }
+
+        let p2_entry = self.p4()
+            .next_table(page.p4_index())
+            .and_then(|p3| p3.next_table(page.p3_index()))
+            .expect("page is not mapped")[page.p2_index()];
+        if p2_entry.flags().contains(HUGE_PAGE) {
+            let table_frame = self.create_split_table(p2_entry, 1, allocator);
+            let p2 = self.p4_mut()
+                .next_table_mut(page.p4_index())
+                .and_then(|p3| p3.next_table_mut(page.p3_index()))
+                .unwrap();
+            install_split_table(p2, page.p2_index(), table_frame, p2_entry.flags());
+        }
+    }
+
+    pub fn unmap<A>(&mut self, page: Page, allocator: &mut A)
+        where A: FrameAllocator
+    {
+        let frame = self.unmap_frame(page, allocator);
         allocator.free(frame);
     }
 
-    pub fn p4(&self) -> &Table<Level4> { unsafe { self.p4.get() }}
-    pub fn p4_mut(&mut self) -> &mut Table<Level4> { unsafe { self.p4.get_mut() }}
+    // like unmap, but the frame is returned instead of freed, e.g. because it
+    // does not belong to the allocator, page tables, which became empty, are still freed
+    pub fn unmap_frame<A>(&mut self, page: Page, allocator: &mut A) -> Frame
+        where A: FrameAllocator
+    {
+        assert!(self.translate(page.start_address()).is_some());
+
+        let frame = {
+            let p1 = self.p4_mut()
+                .next_table_mut(page.p4_index())
+                .and_then(|p3| p3.next_table_mut(page.p3_index()))
+                .and_then(|p2| p2.next_table_mut(page.p2_index()))
+                .expect("page is mapped by a huge page, use unmap_huge_* or split_huge_page");
+            let frame = p1[page.p1_index()].frame().unwrap();
+            p1[page.p1_index()].set_unused();
+            frame
+        };
+
+        unsafe { tlb::flush(page.start_address()); }
+        self.free_empty_tables(page, allocator);
+        frame
+    }
+
+    // frees the P1, P2 and P3 table of page, if they became empty
+    fn free_empty_tables<A>(&mut self, page: Page, allocator: &mut A)
+        where A: FrameAllocator
+    {
+        // the recursive mapping must never be removed
+        if page.p4_index() == ENTRY_COUNT - 1 {
+            return;
+        }
+
+        if let Some(p2) = self.p4_mut()
+            .next_table_mut(page.p4_index())
+            .and_then(|p3| p3.next_table_mut(page.p3_index())) {
+            free_table_if_empty(p2, page.p2_index(), allocator);
+        }
+        if let Some(p3) = self.p4_mut().next_table_mut(page.p4_index()) {
+            free_table_if_empty(p3, page.p3_index(), allocator);
+        }
+        free_table_if_empty(self.p4_mut(), page.p4_index(), allocator);
+    }
+
+    // returns a table of the next lower level, which maps the same memory with the same flags
+    // as the huge page in entry, frames_per_entry is the size of an entry of the new table
+    fn create_split_table<A>(&mut self, entry: Entry, frames_per_entry: usize,
+                             allocator: &mut A) -> Frame
+        where A: FrameAllocator
+    {
+        let start_frame = entry.frame().unwrap();
+        let flags = entry.flags();
+        // the new table only contains huge pages, if it is not a P1 table
+        let entry_flags = if frames_per_entry == 1 { flags - HUGE_PAGE } else { flags };
+        let table_frame = allocator.alloc().expect("Out of Memory!");
+
+        // the table is filled completely before it is installed
+        let mut tmp_page = TemporaryPage::new(Page::containing_address(SPLIT_TEMPORARY_PAGE),
+                                              allocator);
+        {
+            let table = tmp_page.map_table_frame(table_frame.clone(), self);
+            for (i, entry) in table.entries_mut().enumerate() {
+                entry.set(Frame { number: start_frame.number + i * frames_per_entry }, entry_flags);
+            }
+        }
+        tmp_page.unmap(self);
+        tmp_page.release(allocator);
+        table_frame
+    }
+
+    pub fn p4(&self) -> &Table<Level4> { unsafe { self.p4.as_ref() }}
+    pub fn p4_mut(&mut self) -> &mut Table<Level4> { unsafe { self.p4.as_mut() }}
+}
+
+// replaces the huge page in table[index] with the filled table in table_frame,
+// the memory stays mapped, because the entry is replaced with a single write
+fn install_split_table<L>(table: &mut Table<L>, index: usize, table_frame: Frame, flags: EntryFlags)
+    where L: HierarchicalLevel
+{
+    table[index].set(table_frame, PRESENT | WRITABLE | (flags & USER_ACCESSIBLE));
+    unsafe { tlb::flush_all(); }
+}
+
+// removes the table referenced by table[index] and frees its frame, if it has no used entries
+fn free_table_if_empty<L, A>(table: &mut Table<L>, index: usize, allocator: &mut A)
+    where L: HierarchicalLevel, A: FrameAllocator
+{
+    let next_address = match table.next_table(index) {
+        Some(next) if next.is_empty() => next as *const _ as usize,
+        _ => return,
+    };
+
+    let frame = table[index].frame().unwrap();
+    table[index].set_unused();
+    // also invalidates the paging structure caches
+    unsafe { tlb::flush(next_address); }
+    allocator.free(frame);
 }
@@ -1,4 +1,6 @@
 pub use self::table::PAGE_TABLE;
+pub use self::table::PageTable;
+pub use self::entry::*;
 
 mod entry;
 mod table;
@@ -6,11 +8,11 @@ mod temporary_page;
 mod mapper;
 
 use multiboot2::BootInformation;
-use self::entry::EntryFlags;
-use self::entry::{PRESENT, WRITABLE};
+use x86::shared::{control_regs, msr};
 use memory::{FrameAllocator, FRAME_SIZE, Frame};
-use self::table::{PageTable};
+use memory::stack_allocator::boot_stack_guard;
 use self::temporary_page::{TemporaryPage};
+use self::table::ENTRY_COUNT;
 
 // must be the same
 pub const PAGE_SIZE: usize = FRAME_SIZE;
@@ -23,7 +25,7 @@ pub struct Page {
 }
 
 impl Page {
-    fn containing_address(address: VirtualAddress) -> Page {
+    pub fn containing_address(address: VirtualAddress) -> Page {
         assert!(address < 0x0000_8000_0000_0000 ||
                 address >= 0xffff_8000_0000_0000,
                 "invalid address: {:#x}", address);
@@ -35,7 +37,28 @@ impl Page {
     fn p2_index(&self) -> usize { (self.number >> 9) & 0o777 }
     fn p1_index(&self) -> usize { (self.number >> 0) & 0o777 }
 
-    fn start_address(&self) -> usize { self.number * PAGE_SIZE }
+    pub fn start_address(&self) -> usize { self.number * PAGE_SIZE }
+
+    pub fn range_inclusive(start: Page, end: Page) -> PageIter {
+        PageIter {current: start, end: end}
+    }
+}
+
+pub struct PageIter {
+    current: Page, end: Page
+}
+
+impl Iterator for PageIter {
+    type Item = Page;
+
+    fn next(&mut self) -> Option<Page> {
+        if self.current.number > self.end.number {
+            return None;
+        }
+        let page = self.current;
+        self.current.number += 1;
+        Some(page)
+    }
 }
 
 pub struct InactivePageTable {
@@ -67,10 +90,12 @@ pub fn remap_the_kernel<A>(active_table: &mut PageTable,
     assert!(active_table.translate_page(tmp_page_page.clone()).is_none());
     let mut tmp_page = TemporaryPage::new(tmp_page_page, allocator);
 
-    // we leak one frame here, but we don't care
     let tmp_frame = allocator.alloc().expect("out of memory");
     let mut new_page_table = InactivePageTable::new(tmp_frame, active_table, &mut tmp_page);
 
+    // the guard page below the boot stack is part of the .bss and is left unmapped
+    let stack_guard_frame = Frame::containing_address(boot_stack_guard().start_address());
+
     active_table.with(&mut new_page_table, &mut tmp_page, |mapper| {
         let elf_sections_tag = boot_info.elf_sections_tag()
             .expect("expected elf sections tag");
@@ -84,35 +109,72 @@ pub fn remap_the_kernel<A>(active_table: &mut PageTable,
             assert!(section.start_address() % PAGE_SIZE == 0,
                     "sections must be page aligned");
 
-            println!("")
+            let flags = EntryFlags::from_elf_section_flags(section);
 
             for frame in Frame::range_inclusive(
                 Frame::containing_address(section.start_address()),
                 // end address is exclusive
                 Frame::containing_address(section.end_address() - 1)
             ) {
-                mapper.identity_map(frame, WRITABLE, allocator);
+                if frame == stack_guard_frame {
+                    continue;
+                }
+                mapper.identity_map(frame, flags, allocator);
             }
         }
+
+        // identity map the CGA text buffer
+        let cga_buffer_frame = Frame::containing_address(0xb8000);
+        mapper.identity_map(cga_buffer_frame, WRITABLE | NO_EXECUTE, allocator);
+
+        // identity map the multiboot info structure
+        let multiboot_start = boot_info as *const BootInformation as usize;
+        let multiboot_end = multiboot_start + (boot_info.total_size as usize);
+        for frame in Frame::range_inclusive(Frame::containing_address(multiboot_start),
+                                            Frame::containing_address(multiboot_end - 1)) {
+            mapper.identity_map(frame, NO_EXECUTE, allocator);
+        }
     });
+
+    enable_nxe_bit();
+    enable_write_protect_bit();
+    // the old table was set up by the boot code in the .bss, so it must not be freed
+    active_table.switch(new_page_table);
+    tmp_page.release(allocator);
+}
+
+// needed for the NO_EXECUTE bit, otherwise it is a reserved bit
+fn enable_nxe_bit() {
+    const NXE_BIT: u64 = 1 << 11;
+    unsafe {
+        let efer = msr::rdmsr(msr::IA32_EFER);
+        msr::wrmsr(msr::IA32_EFER, efer | NXE_BIT);
+    }
+}
+
+// the kernel must not write to read only pages
+fn enable_write_protect_bit() {
+    unsafe { control_regs::cr0_write(control_regs::cr0() | control_regs::CR0_WRITE_PROTECT); }
 }
 
 use cga_screen::CGAScreen;
 pub fn test_paging<A>(screen: &mut CGAScreen, page_table: &mut PageTable, allocator: &mut A)
     where A: FrameAllocator {
 
-    println!(screen, "translate Some({}): {:?}", 0, page_table.translate(0));
-    println!(screen, "translate Some({}): {:?}", 1024, page_table.translate(1024));
-    println!(screen, "translate Some({}): {:?}", (1 << 30) - 1,
+    // after the remap only the kernel, the CGA buffer and the multiboot info are mapped
+    println!(screen, "translate None({}): {:?}", 0, page_table.translate(0));
+    println!(screen, "translate None({}): {:?}", 1024, page_table.translate(1024));
+    println!(screen, "translate Some({:#x}): {:?}", 0xb8000, page_table.translate(0xb8000));
+    println!(screen, "translate None({}): {:?}", (1 << 30) - 1,
              page_table.translate((1 << 30) - 1));
-    println!(screen, "translate ({}) None: {:?}", 1 << 30, page_table.translate(1 << 30));
+    println!(screen, "translate None({}): {:?}", 1 << 30, page_table.translate(1 << 30));
     let addr = 42 * 512 * 512 * 4096; // 42th P3 entry
     let page = Page::containing_address(addr);
     let frame = allocator.alloc().expect("no more frames");
     println!(screen, "None = {:?}, map to {:?}",
              page_table.translate(addr),
              frame);
-    page_table.map_to(page, frame.clone(), EntryFlags::empty(), allocator);
+    page_table.map_to(page, frame.clone(), WRITABLE, allocator);
     println!(screen, "translate {}, Some = {:?}", addr, page_table.translate(addr));
     println!(screen, "next free frame: {:?}", allocator.alloc());
     // the following line should panic
@@ -131,7 +193,74 @@ pub fn test_paging<A>(screen: &mut CGAScreen, page_table: &mut PageTable, alloca
     println!(screen, "{:?} = {:?}", page_table.translate(addr), page_table.translate(addr4));
     unsafe {*(addr as *mut _) = 42;}
     println!(screen, "read 42: {}", unsafe {*(addr4 as *const usize)});
-    page_table.unmap(Page::containing_address(addr4), allocator);
+    // the frame is still mapped at addr, so only the mapping is removed
+    page_table.unmap_frame(Page::containing_address(addr4), allocator);
     // the following line should cause a page fault
     //println!(screen, "read 42: {}", unsafe {*(addr4 as *const usize)});
 }
+
+// a round trip through PageTable::with must not leak any frames
+pub fn test_temporary_page<A>(screen: &mut CGAScreen, page_table: &mut PageTable, allocator: &mut A)
+    where A: FrameAllocator {
+    let mut allocator = CountingAllocator {inner: allocator, outstanding: 0};
+    {
+        let mut tmp_page = TemporaryPage::new(Page::containing_address(0xcafebabe), &mut allocator);
+        let frame = allocator.alloc().expect("out of memory");
+        let mut table = InactivePageTable::new(frame, page_table, &mut tmp_page);
+        page_table.with(&mut table, &mut tmp_page, |mapper| {
+            assert!(mapper.translate(0xb8000).is_none());
+        });
+        tmp_page.release(&mut allocator);
+        allocator.free(table.p4_frame);
+    }
+    assert_eq!(allocator.outstanding, 0);
+    println!(screen, "temporary page: no frames leaked");
+}
+
+// maps the first 2MiB of physical memory with a huge page, splits and unmaps it again,
+// the memory does not belong to the allocator, but all page tables must be freed
+pub fn test_huge_page<A>(screen: &mut CGAScreen, page_table: &mut PageTable, allocator: &mut A)
+    where A: FrameAllocator {
+    let mut allocator = CountingAllocator {inner: allocator, outstanding: 0};
+    let huge_page_size = ENTRY_COUNT * PAGE_SIZE;
+    let addr = 43 * 512 * 512 * 4096; // 43th P3 entry
+    let offset = 5 * PAGE_SIZE + 42;
+
+    let page = Page::containing_address(addr);
+    page_table.map_to_huge_2m(page, Frame::containing_address(0), EntryFlags::empty(),
+                              &mut allocator);
+    assert_eq!(page_table.translate(addr + offset), Some(offset));
+
+    page_table.split_huge_page(Page::containing_address(addr + offset), &mut allocator);
+    assert_eq!(page_table.translate(addr + offset), Some(offset));
+    assert_eq!(page_table.translate(addr + huge_page_size - 1), Some(huge_page_size - 1));
+
+    let last_page = Page::containing_address(addr + huge_page_size - 1);
+    for page in Page::range_inclusive(page, last_page) {
+        page_table.unmap_frame(page, &mut allocator);
+    }
+    assert!(page_table.translate(addr + offset).is_none());
+    assert_eq!(allocator.outstanding, 0);
+    println!(screen, "huge page: split and unmapped, no frames leaked");
+}
+
+// counts the frames, which were allocated, but not freed again
+struct CountingAllocator<'a, A: 'a + FrameAllocator> {
+    inner: &'a mut A,
+    outstanding: isize,
+}
+
+impl<'a, A> FrameAllocator for CountingAllocator<'a, A> where A: FrameAllocator {
+    fn alloc(&mut self) -> Option<Frame> {
+        let frame = self.inner.alloc();
+        if frame.is_some() {
+            self.outstanding += 1;
+        }
+        frame
+    }
+
+    fn free(&mut self, frame: Frame) {
+        self.outstanding -= 1;
+        self.inner.free(frame);
+    }
+}
@@ -44,6 +44,16 @@ impl PageTable {
 
         temporary_page.unmap(self);
     }
+
+    // activates the new table and returns the previously active one
+    pub fn switch(&mut self, new_table: InactivePageTable) -> InactivePageTable {
+        let old_table = InactivePageTable {
+            p4_frame: Frame::containing_address(unsafe {control_regs::cr3()}),
+        };
+        // the recursive mapping makes the mapper use the new table automatically
+        unsafe {control_regs::cr3_write(new_table.p4_frame.start_address());}
+        old_table
+    }
 }
 
 impl Deref for PageTable {
@@ -85,6 +95,14 @@ impl<L> Table<L> where L: TableLevel {
             entry.set_unused();
         }
     }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.iter().all(|entry| entry.is_unused())
+    }
+
+    pub fn entries_mut(&mut self) -> ::core::slice::IterMut<Entry> {
+        self.entries.iter_mut()
+    }
 }
 
 impl<L> Table<L> where L: HierarchicalLevel {
@@ -102,7 +120,8 @@ impl<L> Table<L> where L: HierarchicalLevel {
                                 -> &mut Table<L::NextLevel>
         where A : FrameAllocator {
         if self.next_table_mut(index).is_none() {
-            assert!(!self[index].flags().contains(HUGE_PAGE), "Huge pages not supported!");
+            assert!(!self[index].flags().contains(HUGE_PAGE),
+                    "the entry maps a huge page, split it first");
             let table_frame = allocator.alloc().expect("Out of Memory!");
             self[index].set(table_frame, PRESENT | WRITABLE);
             self.next_table_mut(index).unwrap().zero()
@@ -1,4 +1,5 @@
-use super::{Page, PageTable, VirtualAddress};
+use super::{Page, VirtualAddress};
+use super::mapper::Mapper;
 use super::table::{Table, Level1};
 use memory::{Frame, FrameAllocator};
 
@@ -16,7 +17,7 @@ impl TemporaryPage {
         }
     }
 
-    pub fn map(&mut self, frame: Frame, active_table: &mut PageTable) -> VirtualAddress {
+    pub fn map(&mut self, frame: Frame, active_table: &mut Mapper) -> VirtualAddress {
         use super::entry::WRITABLE;
 
         assert!(active_table.translate_page(self.page).is_none(),
@@ -25,14 +26,20 @@ impl TemporaryPage {
         self.page.start_address()
     }
 
-    pub fn unmap(&mut self, active_table: &mut PageTable) {
-        active_table.unmap(self.page, &mut self.allocator);
+    // the mapped frame is not freed, only the page tables go back to the tiny allocator
+    pub fn unmap(&mut self, active_table: &mut Mapper) {
+        active_table.unmap_frame(self.page, &mut self.allocator);
+    }
+
+    // gives the frames, which are reserved for page tables, back to the allocator
+    pub fn release<A>(self, allocator: &mut A) where A: FrameAllocator {
+        self.allocator.release(allocator);
     }
 
     // maps the page on the frame and interprets it as a Level1 Table
     pub fn map_table_frame(&mut self,
                            frame: Frame,
-                           active_table: &mut PageTable) -> &mut Table<Level1> {
+                           active_table: &mut Mapper) -> &mut Table<Level1> {
         unsafe { &mut *(self.map(frame, active_table) as *mut Table<Level1>) }
     }
 }
@@ -42,10 +49,15 @@ struct TinyAllocator([Option<Frame>; 3]);
 
 impl TinyAllocator {
     pub fn new<A>(a: &mut A) -> TinyAllocator where A: FrameAllocator{
-        // TODO free these pages in drop
         let mut f = || a.alloc();
         TinyAllocator([f(), f(), f()])
     }
+
+    fn release<A>(mut self, a: &mut A) where A: FrameAllocator {
+        while let Some(frame) = self.alloc() {
+            a.free(frame);
+        }
+    }
 }
 
 impl FrameAllocator for TinyAllocator {
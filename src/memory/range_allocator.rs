@@ -2,11 +2,13 @@ use memory::{Frame, FrameAllocator};
 use multiboot2::{MemoryAreaIter};
 use core::cmp::max;
 
+#[allow(dead_code)]
 pub struct RangeAllocator {
     next_free_frame: Frame,
     end: Frame,
 }
 
+#[allow(dead_code)]
 impl RangeAllocator {
     #[allow(unused_variables)]
     pub fn new(areas: MemoryAreaIter,
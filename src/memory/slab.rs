@@ -0,0 +1,246 @@
+use core::mem::{size_of};
+use spin::Mutex;
+use memory::FrameAllocator;
+use memory::paging::{Page, PageTable, PAGE_SIZE, WRITABLE, NO_EXECUTE};
+
+// the slabs start at 2GiB (P3 entry 2 of the first P4 entry), every slab is exactly one page
+pub const SLAB_START: usize = 0o_000_002_000_000_0000;
+const SLAB_PAGES: usize = 4096;
+const BITS: usize = 64;
+
+pub const CACHE_COUNT: usize = 7;
+const OBJECT_SIZES: [usize; CACHE_COUNT] = [16, 32, 64, 128, 256, 512, 1024];
+// for the smallest object size
+const MAX_OBJECTS_PER_SLAB: usize = PAGE_SIZE / 16;
+
+pub static SLAB_ALLOCATOR: Mutex<SlabAllocator> = Mutex::new(SlabAllocator::new());
+
+// stored at the end of every slab page, the objects start at the beginning of the page
+struct SlabHeader {
+    // next slab of the same cache
+    next: *mut SlabHeader,
+    // one bit per object, a set bit means, that the object is free
+    free_objects: [u64; MAX_OBJECTS_PER_SLAB / BITS],
+    in_use: usize,
+    cache: usize,
+}
+
+struct Cache {
+    object_size: usize,
+    slabs: *mut SlabHeader,
+    objects_in_use: usize,
+    slab_count: usize,
+}
+
+#[derive(Debug, Clone, Copy)]
+pub struct CacheStats {
+    pub object_size: usize,
+    pub objects_in_use: usize,
+    pub slabs: usize,
+    pub pages: usize,
+}
+
+pub struct SlabAllocator {
+    caches: [Cache; CACHE_COUNT],
+    // one bit per page of the slab region, a set bit means, that the page is mapped
+    used_pages: [u64; SLAB_PAGES / BITS],
+}
+
+// the slabs are only accessed through the mutex
+unsafe impl Send for SlabAllocator {}
+
+impl SlabAllocator {
+    const fn new() -> SlabAllocator {
+        SlabAllocator {
+            // must match OBJECT_SIZES
+            caches: [Cache::new(16), Cache::new(32), Cache::new(64), Cache::new(128),
+                     Cache::new(256), Cache::new(512), Cache::new(1024)],
+            used_pages: [0; SLAB_PAGES / BITS],
+        }
+    }
+
+    // returns an object of at least size bytes, which is aligned to its object size
+    pub fn alloc<A>(&mut self, size: usize, page_table: &mut PageTable, allocator: &mut A)
+                    -> Option<*mut u8> where A: FrameAllocator {
+        let cache = match OBJECT_SIZES.iter().position(|&object_size| object_size >= size) {
+            Some(cache) => cache,
+            None => return None,
+        };
+
+        let object_size = self.caches[cache].object_size;
+        let mut slab = self.caches[cache].slabs;
+        unsafe {
+            while !slab.is_null() && (*slab).in_use == objects_per_slab(object_size) {
+                slab = (*slab).next;
+            }
+        }
+        if slab.is_null() {
+            slab = match self.grow(cache, page_table, allocator) {
+                Some(slab) => slab,
+                None => return None,
+            };
+        }
+
+        self.caches[cache].objects_in_use += 1;
+        unsafe {
+            let (index, word) = (*slab).free_objects.iter().enumerate()
+                .find(|&(_, word)| *word != 0)
+                .map(|(index, word)| (index, *word))
+                .expect("slab has no free object");
+            let object = index * BITS + word.trailing_zeros() as usize;
+            (*slab).free_objects[index] &= !(1 << (object % BITS));
+            (*slab).in_use += 1;
+            let page = Page::containing_address(slab as usize);
+            Some((page.start_address() + object * object_size) as *mut u8)
+        }
+    }
+
+    // object must have been returned by alloc
+    pub unsafe fn free(&mut self, object: *mut u8) {
+        let address = object as usize;
+        assert!(address >= SLAB_START && address < SLAB_START + SLAB_PAGES * PAGE_SIZE,
+                "{:#x} is not a slab object", address);
+
+        let slab = header(Page::containing_address(address));
+        let object_size = self.caches[(*slab).cache].object_size;
+        assert!((address % PAGE_SIZE) % object_size == 0, "{:#x} is not a slab object", address);
+
+        let object = (address % PAGE_SIZE) / object_size;
+        let bit = 1 << (object % BITS);
+        assert!((*slab).free_objects[object / BITS] & bit == 0,
+                "double free of slab object {:#x}", address);
+        (*slab).free_objects[object / BITS] |= bit;
+        (*slab).in_use -= 1;
+        self.caches[(*slab).cache].objects_in_use -= 1;
+    }
+
+    // unmaps all slabs without used objects and returns the number of freed pages
+    pub fn shrink<A>(&mut self, page_table: &mut PageTable, allocator: &mut A) -> usize
+        where A: FrameAllocator {
+        let mut freed = 0;
+        for cache in 0..CACHE_COUNT {
+            let mut previous: *mut *mut SlabHeader = &mut self.caches[cache].slabs;
+            unsafe {
+                while !(*previous).is_null() {
+                    let slab = *previous;
+                    if (*slab).in_use != 0 {
+                        previous = &mut (*slab).next;
+                        continue;
+                    }
+
+                    *previous = (*slab).next;
+                    let page = Page::containing_address(slab as usize);
+                    page_table.unmap(page, allocator);
+                    self.set_page_used(page, false);
+                    self.caches[cache].slab_count -= 1;
+                    freed += 1;
+                }
+            }
+        }
+        freed
+    }
+
+    pub fn stats(&self) -> [CacheStats; CACHE_COUNT] {
+        let mut stats = [CacheStats { object_size: 0, objects_in_use: 0, slabs: 0, pages: 0 };
+                         CACHE_COUNT];
+        for (stat, cache) in stats.iter_mut().zip(self.caches.iter()) {
+            *stat = CacheStats {
+                object_size: cache.object_size,
+                objects_in_use: cache.objects_in_use,
+                slabs: cache.slab_count,
+                // every slab is exactly one page
+                pages: cache.slab_count,
+            };
+        }
+        stats
+    }
+
+    fn grow<A>(&mut self, cache: usize, page_table: &mut PageTable, allocator: &mut A)
+               -> Option<*mut SlabHeader> where A: FrameAllocator {
+        let page = match self.find_free_page() {
+            Some(page) => page,
+            None => return None,
+        };
+        let frame = match allocator.alloc() {
+            Some(frame) => frame,
+            None => return None,
+        };
+        page_table.map_to(page, frame, WRITABLE | NO_EXECUTE, allocator);
+        self.set_page_used(page, true);
+
+        let object_size = self.caches[cache].object_size;
+        let slab = header(page);
+        unsafe {
+            *slab = SlabHeader {
+                next: self.caches[cache].slabs,
+                free_objects: [0; MAX_OBJECTS_PER_SLAB / BITS],
+                in_use: 0,
+                cache: cache,
+            };
+
+            for object in 0..objects_per_slab(object_size) {
+                (*slab).free_objects[object / BITS] |= 1 << (object % BITS);
+            }
+        }
+
+        self.caches[cache].slabs = slab;
+        self.caches[cache].slab_count += 1;
+        Some(slab)
+    }
+
+    fn find_free_page(&self) -> Option<Page> {
+        for (index, word) in self.used_pages.iter().enumerate() {
+            if *word != !0 {
+                let number = index * BITS + (!word).trailing_zeros() as usize;
+                return Some(Page::containing_address(SLAB_START + number * PAGE_SIZE));
+            }
+        }
+        None
+    }
+
+    fn set_page_used(&mut self, page: Page, used: bool) {
+        let number = (page.start_address() - SLAB_START) / PAGE_SIZE;
+        if used {
+            self.used_pages[number / BITS] |= 1 << (number % BITS);
+        } else {
+            self.used_pages[number / BITS] &= !(1 << (number % BITS));
+        }
+    }
+}
+
+impl Cache {
+    const fn new(object_size: usize) -> Cache {
+        Cache {
+            object_size: object_size,
+            slabs: 0 as *mut SlabHeader,
+            objects_in_use: 0,
+            slab_count: 0,
+        }
+    }
+}
+
+fn header(page: Page) -> *mut SlabHeader {
+    (page.start_address() + PAGE_SIZE - size_of::<SlabHeader>()) as *mut SlabHeader
+}
+
+fn objects_per_slab(object_size: usize) -> usize {
+    (PAGE_SIZE - size_of::<SlabHeader>()) / object_size
+}
+
+use cga_screen::CGAScreen;
+pub fn test_slab<A>(screen: &mut CGAScreen, page_table: &mut PageTable, allocator: &mut A)
+    where A: FrameAllocator {
+    let mut slab_allocator = SLAB_ALLOCATOR.lock();
+
+    let first = slab_allocator.alloc(24, page_table, allocator).expect("no slab object");
+    let second = slab_allocator.alloc(24, page_table, allocator).expect("no slab object");
+    println!(screen, "slab objects: {:?} {:?}", first, second);
+    unsafe {
+        slab_allocator.free(first);
+        slab_allocator.free(second);
+    }
+
+    println!(screen, "slab stats: {:?}", slab_allocator.stats()[1]);
+    println!(screen, "slab pages freed by shrink: {}",
+             slab_allocator.shrink(page_table, allocator));
+}
@@ -0,0 +1,97 @@
+use spin::Mutex;
+use memory::FrameAllocator;
+use memory::paging::{Page, PageTable, PAGE_SIZE, WRITABLE, NO_EXECUTE};
+
+// the kernel stacks start at 3GiB (P3 entry 3 of the first P4 entry), their P2 table
+// is shared with the temporary page at 0xcafebabe and the APIC registers
+pub const STACK_AREA_START: usize = 0o_000_003_000_000_0000;
+// every stack gets a slot of this size, the stack is mapped at the top of the slot
+// and the pages below it are never mapped and act as guard pages
+const SLOT_PAGES: usize = 16;
+const SLOT_COUNT: usize = 256;
+const BITS: usize = 64;
+pub const STACK_AREA_END: usize = STACK_AREA_START + SLOT_COUNT * SLOT_PAGES * PAGE_SIZE;
+
+pub static STACK_ALLOCATOR: Mutex<StackAllocator> = Mutex::new(StackAllocator {
+    used_slots: [0; SLOT_COUNT / BITS]
+});
+
+extern {
+    // guard page below the boot stack, see boot/startup.asm
+    static stack_guard: u8;
+}
+
+pub fn boot_stack_guard() -> Page {
+    Page::containing_address(unsafe { &stack_guard as *const u8 as usize })
+}
+
+// whether an access to this address is caused by a kernel stack overflow
+pub fn is_stack_guard(address: usize) -> bool {
+    let boot_guard = boot_stack_guard().start_address();
+    if address >= boot_guard && address < boot_guard + PAGE_SIZE {
+        return true;
+    }
+    // the stacks themselves are always mapped, so every fault in this area hits a guard page
+    address >= STACK_AREA_START && address < STACK_AREA_END
+}
+
+#[derive(Debug)]
+pub struct Stack {
+    top: usize,
+    bottom: usize,
+}
+
+impl Stack {
+    // the stack grows downwards, so this is the initial stack pointer
+    pub fn top(&self) -> usize { self.top }
+    pub fn bottom(&self) -> usize { self.bottom }
+}
+
+pub struct StackAllocator {
+    used_slots: [u64; SLOT_COUNT / BITS],
+}
+
+impl StackAllocator {
+    pub fn alloc<A>(&mut self, pages: usize, page_table: &mut PageTable, allocator: &mut A)
+                    -> Option<Stack> where A: FrameAllocator {
+        // there must be at least one guard page below the stack
+        assert!(pages > 0 && pages < SLOT_PAGES, "invalid stack size: {} pages", pages);
+
+        let slot = match self.find_free_slot() {
+            Some(slot) => slot,
+            None => return None,
+        };
+        self.used_slots[slot / BITS] |= 1 << (slot % BITS);
+
+        let top = STACK_AREA_START + (slot + 1) * SLOT_PAGES * PAGE_SIZE;
+        let bottom = top - pages * PAGE_SIZE;
+        for page in Page::range_inclusive(Page::containing_address(bottom),
+                                          Page::containing_address(top - 1)) {
+            page_table.map(page, WRITABLE | NO_EXECUTE, allocator);
+        }
+
+        Some(Stack { top: top, bottom: bottom })
+    }
+
+    pub fn free<A>(&mut self, stack: Stack, page_table: &mut PageTable, allocator: &mut A)
+        where A: FrameAllocator {
+        let slot = (stack.bottom - STACK_AREA_START) / (SLOT_PAGES * PAGE_SIZE);
+        assert!(self.used_slots[slot / BITS] & (1 << (slot % BITS)) != 0,
+                "double free of {:?}", stack);
+
+        for page in Page::range_inclusive(Page::containing_address(stack.bottom),
+                                          Page::containing_address(stack.top - 1)) {
+            page_table.unmap(page, allocator);
+        }
+        self.used_slots[slot / BITS] &= !(1 << (slot % BITS));
+    }
+
+    fn find_free_slot(&self) -> Option<usize> {
+        for (index, word) in self.used_slots.iter().enumerate() {
+            if *word != !0 {
+                return Some(index * BITS + (!word).trailing_zeros() as usize);
+            }
+        }
+        None
+    }
+}
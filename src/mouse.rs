@@ -0,0 +1,174 @@
+use spin::Mutex;
+use interrupts;
+use ring_buffer::RingBuffer;
+use ps2::{self, Device, OUTB, AUXB, AUX_PORT};
+
+// mouse commands
+const GET_ID: u8 = 0xf2;
+const SET_SAMPLE_RATE: u8 = 0xf3;
+const ENABLE_REPORTING: u8 = 0xf4;
+const SET_DEFAULTS: u8 = 0xf6;
+// the id of a mouse with a scroll wheel
+const INTELLIMOUSE_ID: u8 = 3;
+// always set in the first byte of a packet
+const PACKET_SYNC: u8 = 1 << 3;
+const X_SIGN: u8 = 1 << 4;
+const Y_SIGN: u8 = 1 << 5;
+const X_OVERFLOW: u8 = 1 << 6;
+const Y_OVERFLOW: u8 = 1 << 7;
+const MOUSE_IRQ: usize = 12;
+
+bitflags! {
+    pub flags MouseButtons: u8 {
+        const LEFT_BUTTON =   1 << 0,
+        const RIGHT_BUTTON =  1 << 1,
+        const MIDDLE_BUTTON = 1 << 2,
+    }
+}
+
+pub static MOUSE: Mutex<Mouse> = Mutex::new(Mouse {initialized: false, wheel: false});
+
+// filled by the interrupt handler
+static EVENTS: RingBuffer<MouseEvent> = RingBuffer::new(MouseEvent::empty());
+
+// only used by the interrupt handler and during the initialization with interrupts disabled
+static PACKET: Mutex<Packet> = Mutex::new(Packet {bytes: [0; 4], len: 0, size: 3});
+
+struct Packet {
+    bytes: [u8; 4],
+    len: usize,
+    // 4 with a scroll wheel
+    size: usize,
+}
+
+impl Packet {
+    fn add_byte(&mut self, byte: u8) -> Option<MouseEvent> {
+        // resynchronize, if a byte got lost
+        if self.len == 0 && (byte & PACKET_SYNC) == 0 {
+            return None;
+        }
+
+        self.bytes[self.len] = byte;
+        self.len += 1;
+        if self.len < self.size {
+            return None;
+        }
+        self.len = 0;
+
+        let flags = self.bytes[0];
+        if (flags & (X_OVERFLOW | Y_OVERFLOW)) != 0 {
+            return None;
+        }
+
+        // the movement is a 9 bit two's complement number
+        let mut dx = self.bytes[1] as i16;
+        if (flags & X_SIGN) != 0 { dx -= 0x100; }
+        let mut dy = self.bytes[2] as i16;
+        if (flags & Y_SIGN) != 0 { dy -= 0x100; }
+        let dz = if self.size == 4 { self.bytes[3] as i8 } else { 0 };
+
+        Some(MouseEvent {
+            dx: dx, dy: dy, dz: dz,
+            buttons: MouseButtons::from_bits_truncate(flags),
+        })
+    }
+}
+
+fn mouse_interrupt() {
+    let status = ps2::status();
+    if (status & OUTB) != 0 && (status & AUXB) != 0 {
+        receive_byte(ps2::read_data());
+    }
+}
+
+// also used for mouse bytes, which arrive while the keyboard driver waits for a response
+pub fn receive_byte(byte: u8) {
+    // the interrupt handler must not run at the same time
+    interrupts::without_interrupts(|| {
+        if let Some(event) = PACKET.lock().add_byte(byte) {
+            // if the buffer is full, the event is lost
+            EVENTS.push(event);
+        }
+    });
+}
+
+pub struct Mouse {
+    initialized: bool,
+    wheel: bool,
+}
+
+impl Mouse {
+
+    // returns false, if no mouse is connected
+    pub fn init(&mut self) -> bool {
+        assert!(!self.initialized);
+        if !ps2::ports().contains(AUX_PORT) {
+            return false;
+        }
+
+        let present = interrupts::without_interrupts(|| {
+            if !ps2::send_to_device(Device::Mouse, SET_DEFAULTS) {
+                return false;
+            }
+            self.wheel = self.enable_wheel();
+            PACKET.lock().size = if self.wheel { 4 } else { 3 };
+            ps2::send_to_device(Device::Mouse, ENABLE_REPORTING)
+        });
+        if !present {
+            return false;
+        }
+
+        interrupts::register_irq(MOUSE_IRQ, mouse_interrupt);
+        self.initialized = true;
+        true
+    }
+
+    // always None, if no mouse was found
+    pub fn try_read_event(&mut self) -> Option<MouseEvent> {
+        EVENTS.pop()
+    }
+
+    pub fn has_wheel(&self) -> bool { self.wheel }
+
+    // the magic sample rate sequence switches an intellimouse to 4 byte packets
+    fn enable_wheel(&mut self) -> bool {
+        for &rate in &[200, 100, 80] {
+            if !(ps2::send_to_device(Device::Mouse, SET_SAMPLE_RATE) &&
+                 ps2::send_to_device(Device::Mouse, rate)) {
+                return false;
+            }
+        }
+
+        if !ps2::send_to_device(Device::Mouse, GET_ID) {
+            return false;
+        }
+        ps2::read_from_device(Device::Mouse) == Some(INTELLIMOUSE_ID)
+    }
+}
+
+#[derive(Copy, Clone, Debug)]
+pub struct MouseEvent {
+    dx: i16,
+    dy: i16,
+    dz: i8,
+    buttons: MouseButtons,
+}
+
+impl MouseEvent {
+    const fn empty() -> MouseEvent {
+        // MouseButtons::empty() is no const fn
+        MouseEvent {dx: 0, dy: 0, dz: 0, buttons: MouseButtons {bits: 0}}
+    }
+
+    // positive, if the mouse was moved to the right
+    pub fn dx(&self) -> i16 { self.dx }
+    // positive, if the mouse was moved up
+    pub fn dy(&self) -> i16 { self.dy }
+    // positive, if the wheel was scrolled down
+    pub fn dz(&self) -> i8 { self.dz }
+
+    pub fn buttons(&self) -> MouseButtons { self.buttons }
+    pub fn left(&self) -> bool { self.buttons.contains(LEFT_BUTTON) }
+    pub fn right(&self) -> bool { self.buttons.contains(RIGHT_BUTTON) }
+    pub fn middle(&self) -> bool { self.buttons.contains(MIDDLE_BUTTON) }
+}
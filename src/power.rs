@@ -6,3 +6,10 @@ pub fn shutdown() {
     let port = IOPort::new(0xb004);
     port.outw(0 | 0x2000);
 }
+
+pub fn reboot() {
+    // pulse the reset line of the cpu via the keyboard controller
+    let ctrl_port = IOPort::new(0x64);
+    while (ctrl_port.inb() & 0x02) != 0 {}
+    ctrl_port.outb(0xfe);
+}
@@ -0,0 +1,223 @@
+use io_port::{IOPort};
+use spin::Once;
+use keyboard;
+use mouse;
+
+static CTRL_PORT: IOPort = IOPort::new(0x64);
+static DATA_PORT: IOPort = IOPort::new(0x60);
+// status bits
+pub const OUTB: u8 = 0x01;
+const INPB: u8 = 0x02;
+pub const AUXB: u8 = 0x20;
+// controller commands
+const READ_CONFIG: u8 = 0x20;
+const WRITE_CONFIG: u8 = 0x60;
+const DISABLE_AUX: u8 = 0xa7;
+const ENABLE_AUX: u8 = 0xa8;
+const TEST_AUX: u8 = 0xa9;
+const SELF_TEST: u8 = 0xaa;
+const TEST_KEYBOARD: u8 = 0xab;
+const DISABLE_KEYBOARD: u8 = 0xad;
+const ENABLE_KEYBOARD: u8 = 0xae;
+const WRITE_AUX: u8 = 0xd4;
+const SELF_TEST_PASSED: u8 = 0x55;
+const PORT_TEST_PASSED: u8 = 0x00;
+// device responses
+const ACK: u8 = 0xfa;
+const RESEND: u8 = 0xfe;
+const SEND_RETRIES: usize = 3;
+// configuration byte
+const CONFIG_KEYBOARD_IRQ: u8 = 1 << 0;
+const CONFIG_AUX_IRQ: u8 = 1 << 1;
+const CONFIG_KEYBOARD_CLOCK_DISABLED: u8 = 1 << 4;
+const CONFIG_AUX_CLOCK_DISABLED: u8 = 1 << 5;
+const CONFIG_TRANSLATION: u8 = 1 << 6;
+// number of status polls, until the controller or a device is considered unresponsive
+const RESPONSE_TIMEOUT: usize = 100000;
+
+bitflags! {
+    pub flags Ports: u8 {
+        const KEYBOARD_PORT = 1 << 0,
+        const AUX_PORT =      1 << 1,
+    }
+}
+
+#[derive(Copy, Clone, PartialEq, Eq)]
+pub enum Device {
+    Keyboard,
+    Mouse,
+}
+
+static PORTS: Once<Ports> = Once::new();
+
+macro_rules! try_opt {
+    ($e:expr) => (match $e { Some(value) => value, None => return None });
+}
+
+// must be called with interrupts disabled, before the keyboard and the mouse are initialized,
+// without a responding controller no ports are reported
+pub fn init() -> Ports {
+    *PORTS.call_once(|| detect_ports().unwrap_or(Ports::empty()))
+}
+
+// None, if the controller does not respond
+fn detect_ports() -> Option<Ports> {
+    try_opt!(write_command(DISABLE_KEYBOARD));
+    try_opt!(write_command(DISABLE_AUX));
+    flush();
+
+    // no interrupts and no translation during the tests
+    let mut config = try_opt!(read_config());
+    config &= !(CONFIG_KEYBOARD_IRQ | CONFIG_AUX_IRQ | CONFIG_TRANSLATION);
+    try_opt!(write_config(config));
+
+    try_opt!(write_command(SELF_TEST));
+    if read_response() != Some(SELF_TEST_PASSED) {
+        return None;
+    }
+    // some controllers are reset by the self test
+    try_opt!(write_config(config));
+
+    // the aux clock is only enabled by ENABLE_AUX on a dual channel controller
+    let mut dual_channel = false;
+    if (config & CONFIG_AUX_CLOCK_DISABLED) != 0 {
+        try_opt!(write_command(ENABLE_AUX));
+        dual_channel = (try_opt!(read_config()) & CONFIG_AUX_CLOCK_DISABLED) == 0;
+        try_opt!(write_command(DISABLE_AUX));
+    }
+
+    let mut ports = Ports::empty();
+    try_opt!(write_command(TEST_KEYBOARD));
+    if read_response() == Some(PORT_TEST_PASSED) {
+        ports.insert(KEYBOARD_PORT);
+    }
+    if dual_channel {
+        try_opt!(write_command(TEST_AUX));
+        if read_response() == Some(PORT_TEST_PASSED) {
+            ports.insert(AUX_PORT);
+        }
+    }
+
+    // keyboard drivers expect scancode set 1
+    config = try_opt!(read_config()) | CONFIG_TRANSLATION;
+    if ports.contains(KEYBOARD_PORT) {
+        try_opt!(write_command(ENABLE_KEYBOARD));
+        config = (config | CONFIG_KEYBOARD_IRQ) & !CONFIG_KEYBOARD_CLOCK_DISABLED;
+    }
+    if ports.contains(AUX_PORT) {
+        try_opt!(write_command(ENABLE_AUX));
+        config = (config | CONFIG_AUX_IRQ) & !CONFIG_AUX_CLOCK_DISABLED;
+    }
+    try_opt!(write_config(config));
+    flush();
+
+    Some(ports)
+}
+
+// the ports, which passed the tests during init
+pub fn ports() -> Ports {
+    *PORTS.try().expect("ps2 controller not initialized")
+}
+
+pub fn status() -> u8 {
+    CTRL_PORT.inb()
+}
+
+// does not wait for the output buffer
+pub fn read_data() -> u8 {
+    DATA_PORT.inb()
+}
+
+// sends the byte to the device and resends it, if the device asks for it,
+// returns false, if the device did not acknowledge the byte
+pub fn send_to_device(device: Device, byte: u8) -> bool {
+    for _ in 0..SEND_RETRIES {
+        let written = match device {
+            Device::Keyboard => write_data(byte),
+            Device::Mouse => write_command(WRITE_AUX).and_then(|_| write_data(byte)),
+        };
+        if written.is_none() {
+            return false;
+        }
+
+        match read_from_device(device) {
+            Some(ACK) => return true,
+            Some(RESEND) => continue,
+            _ => return false,
+        }
+    }
+    false
+}
+
+// waits for the next byte from the device, bytes of the other device are passed to its driver,
+// interrupts must be disabled, so that the interrupt handlers do not consume the bytes
+pub fn read_from_device(device: Device) -> Option<u8> {
+    for _ in 0..RESPONSE_TIMEOUT {
+        let status = status();
+        if (status & OUTB) == 0 {
+            continue;
+        }
+
+        let byte = read_data();
+        let from = if (status & AUXB) != 0 { Device::Mouse } else { Device::Keyboard };
+        if from == device && !(device == Device::Keyboard && is_key(byte)) {
+            return Some(byte);
+        }
+        match from {
+            Device::Keyboard => keyboard::receive_scancode(byte),
+            Device::Mouse => mouse::receive_byte(byte),
+        }
+    }
+    None
+}
+
+// keys pressed, while the keyboard driver waits for a response, are no response
+fn is_key(byte: u8) -> bool {
+    byte != ACK && byte != RESEND
+}
+
+fn write_data(byte: u8) -> Option<()> {
+    try_opt!(wait_for_input_buffer());
+    DATA_PORT.outb(byte);
+    Some(())
+}
+
+fn write_command(cmd: u8) -> Option<()> {
+    try_opt!(wait_for_input_buffer());
+    CTRL_PORT.outb(cmd);
+    Some(())
+}
+
+fn read_config() -> Option<u8> {
+    try_opt!(write_command(READ_CONFIG));
+    read_response()
+}
+
+fn write_config(config: u8) -> Option<()> {
+    try_opt!(write_command(WRITE_CONFIG));
+    write_data(config)
+}
+
+fn read_response() -> Option<u8> {
+    for _ in 0..RESPONSE_TIMEOUT {
+        if (status() & OUTB) != 0 {
+            return Some(read_data());
+        }
+    }
+    None
+}
+
+fn wait_for_input_buffer() -> Option<()> {
+    for _ in 0..RESPONSE_TIMEOUT {
+        if (status() & INPB) == 0 {
+            return Some(());
+        }
+    }
+    None
+}
+
+fn flush() {
+    while (status() & OUTB) != 0 {
+        read_data();
+    }
+}
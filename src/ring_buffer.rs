@@ -0,0 +1,51 @@
+use core::cell::UnsafeCell;
+use core::sync::atomic::{AtomicUsize, Ordering};
+
+pub const RING_BUFFER_SIZE: usize = 128;
+
+// lock free queue for exactly one producer (e.g. an interrupt handler) and one consumer,
+// another context may only push, while the producer is guaranteed not to run (e.g. its irq is masked),
+// one slot is always left empty to distinguish a full from an empty buffer
+pub struct RingBuffer<T: Copy> {
+    buffer: UnsafeCell<[T; RING_BUFFER_SIZE]>,
+    // next element to read, only written by the consumer
+    head: AtomicUsize,
+    // next free slot, only written by the producer
+    tail: AtomicUsize,
+}
+
+unsafe impl<T: Copy + Send> Sync for RingBuffer<T> {}
+
+impl<T: Copy> RingBuffer<T> {
+    pub const fn new(empty: T) -> RingBuffer<T> {
+        RingBuffer {
+            buffer: UnsafeCell::new([empty; RING_BUFFER_SIZE]),
+            head: AtomicUsize::new(0),
+            tail: AtomicUsize::new(0),
+        }
+    }
+
+    // returns false, if the buffer is full and the value was dropped
+    pub fn push(&self, value: T) -> bool {
+        let tail = self.tail.load(Ordering::Relaxed);
+        let next = (tail + 1) % RING_BUFFER_SIZE;
+        if next == self.head.load(Ordering::Acquire) {
+            return false;
+        }
+
+        unsafe { (*self.buffer.get())[tail] = value; }
+        self.tail.store(next, Ordering::Release);
+        true
+    }
+
+    pub fn pop(&self) -> Option<T> {
+        let head = self.head.load(Ordering::Relaxed);
+        if head == self.tail.load(Ordering::Acquire) {
+            return None;
+        }
+
+        let value = unsafe { (*self.buffer.get())[head] };
+        self.head.store((head + 1) % RING_BUFFER_SIZE, Ordering::Release);
+        Some(value)
+    }
+}
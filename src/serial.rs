@@ -0,0 +1,170 @@
+use core::fmt;
+use core::sync::atomic::{AtomicBool, Ordering};
+use spin::Mutex;
+use io_port::{IOPort};
+use interrupts;
+use ring_buffer::RingBuffer;
+
+// register offsets, the divisor registers replace data and interrupt enable while DLAB is set
+const DATA: u16 = 0;
+const INTERRUPT_ENABLE: u16 = 1;
+const DIVISOR_LOW: u16 = 0;
+const DIVISOR_HIGH: u16 = 1;
+const FIFO_CONTROL: u16 = 2;
+const LINE_CONTROL: u16 = 3;
+const MODEM_CONTROL: u16 = 4;
+const LINE_STATUS: u16 = 5;
+
+const UART_CLOCK: u32 = 115200;
+const DLAB: u8 = 0x80;
+// 8 data bits, no parity, one stop bit
+const LINE_8N1: u8 = 0x03;
+// enable and clear the fifos, interrupt at 14 bytes
+const FIFO_ENABLE: u8 = 0xc7;
+// DTR, RTS and OUT2, which connects the interrupt line
+const MODEM_READY: u8 = 0x0b;
+const MODEM_LOOPBACK: u8 = 0x1e;
+const LOOPBACK_TEST_BYTE: u8 = 0xae;
+const RECEIVED_DATA_INTERRUPT: u8 = 0x01;
+const DATA_READY: u8 = 0x01;
+const TRANSMIT_EMPTY: u8 = 0x20;
+
+const PORT_COUNT: usize = 4;
+const BASES: [u16; PORT_COUNT] = [0x3f8, 0x2f8, 0x3e8, 0x2e8];
+// COM1 and COM3 share irq 4, COM2 and COM4 share irq 3
+const IRQS: [usize; PORT_COUNT] = [4, 3, 4, 3];
+
+pub static COM1: Mutex<SerialPort> = Mutex::new(SerialPort::new(0));
+#[allow(dead_code)]
+pub static COM2: Mutex<SerialPort> = Mutex::new(SerialPort::new(1));
+#[allow(dead_code)]
+pub static COM3: Mutex<SerialPort> = Mutex::new(SerialPort::new(2));
+#[allow(dead_code)]
+pub static COM4: Mutex<SerialPort> = Mutex::new(SerialPort::new(3));
+
+// filled by the interrupt handlers
+static RECEIVED: [RingBuffer<u8>; PORT_COUNT] = [RingBuffer::new(0), RingBuffer::new(0),
+                                                 RingBuffer::new(0), RingBuffer::new(0)];
+// the interrupt handlers must only read initialized ports
+static ENABLED: [AtomicBool; PORT_COUNT] = [AtomicBool::new(false), AtomicBool::new(false),
+                                            AtomicBool::new(false), AtomicBool::new(false)];
+
+macro_rules! serial_println {
+    ($fmt:expr) => (serial_print!(concat!($fmt, "\n")));
+    ($fmt:expr, $($arg:tt)*) => (serial_print!(concat!($fmt, "\n"), $($arg)*));
+}
+
+// prints to COM1
+macro_rules! serial_print {
+    ($($arg:tt)*) => ({
+        $crate::serial::print(format_args!($($arg)*));
+    });
+}
+
+pub fn print(args: fmt::Arguments) {
+    use core::fmt::Write;
+    // an interrupt handler printing to COM1 must not find it locked
+    interrupts::without_interrupts(|| {
+        COM1.lock().write_fmt(args).unwrap();
+    });
+}
+
+fn com1_com3_interrupt() {
+    receive(0);
+    receive(2);
+}
+
+fn com2_com4_interrupt() {
+    receive(1);
+    receive(3);
+}
+
+fn receive(index: usize) {
+    if !ENABLED[index].load(Ordering::Acquire) {
+        return;
+    }
+    let port = SerialPort::new(index);
+    while (port.register(LINE_STATUS).inb() & DATA_READY) != 0 {
+        // if the buffer is full, the byte is lost
+        RECEIVED[index].push(port.register(DATA).inb());
+    }
+}
+
+pub struct SerialPort {
+    // COM1 is 0
+    index: usize,
+}
+
+impl SerialPort {
+    const fn new(index: usize) -> SerialPort {
+        SerialPort {index: index}
+    }
+
+    // returns false, if there is no uart at this port
+    pub fn init(&mut self, baud: u32) -> bool {
+        assert!(!self.is_enabled());
+        assert!(baud > 0 && UART_CLOCK % baud == 0, "unsupported baud rate {}", baud);
+
+        self.register(INTERRUPT_ENABLE).outb(0);
+        let divisor = UART_CLOCK / baud;
+        self.register(LINE_CONTROL).outb(DLAB);
+        self.register(DIVISOR_LOW).outb(divisor as u8);
+        self.register(DIVISOR_HIGH).outb((divisor >> 8) as u8);
+        self.register(LINE_CONTROL).outb(LINE_8N1);
+        self.register(FIFO_CONTROL).outb(FIFO_ENABLE);
+
+        // the sent byte must come back in loopback mode
+        self.register(MODEM_CONTROL).outb(MODEM_LOOPBACK);
+        self.register(DATA).outb(LOOPBACK_TEST_BYTE);
+        if self.register(DATA).inb() != LOOPBACK_TEST_BYTE {
+            return false;
+        }
+        self.register(MODEM_CONTROL).outb(MODEM_READY);
+
+        // the irq line is shared with another port, which may have registered it already
+        let partner = self.index ^ 2;
+        let registered = ENABLED[partner].load(Ordering::Acquire);
+        ENABLED[self.index].store(true, Ordering::Release);
+        if !registered {
+            let handler: fn() = if IRQS[self.index] == 4 { com1_com3_interrupt } else { com2_com4_interrupt };
+            interrupts::register_irq(IRQS[self.index], handler);
+        }
+        self.register(INTERRUPT_ENABLE).outb(RECEIVED_DATA_INTERRUPT);
+        true
+    }
+
+    pub fn is_enabled(&self) -> bool {
+        ENABLED[self.index].load(Ordering::Acquire)
+    }
+
+    #[allow(dead_code)]
+    pub fn read_byte(&mut self) -> Option<u8> {
+        RECEIVED[self.index].pop()
+    }
+
+    // dropped, if the port is not initialized
+    pub fn write_byte(&mut self, byte: u8) {
+        if !self.is_enabled() {
+            return;
+        }
+        while (self.register(LINE_STATUS).inb() & TRANSMIT_EMPTY) == 0 {}
+        self.register(DATA).outb(byte);
+    }
+
+    fn register(&self, offset: u16) -> IOPort {
+        IOPort::new(BASES[self.index] + offset)
+    }
+}
+
+impl fmt::Write for SerialPort {
+    fn write_str(&mut self, s: &str) -> fmt::Result {
+        for byte in s.bytes() {
+            // terminals expect a carriage return
+            if byte == b'\n' {
+                self.write_byte(b'\r');
+            }
+            self.write_byte(byte);
+        }
+        Ok(())
+    }
+}